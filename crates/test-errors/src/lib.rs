@@ -0,0 +1,128 @@
+//! Macros that must be rejected with an error instead of crashing or hanging the compiler. Every
+//! example is a doctest that must fail to compile.
+//!
+//! Functions that call themselves reach the recursion limit:
+//!
+//! ```compile_fail
+//! inception::rules! {
+//!     use rust_parser::v1;
+//!
+//!     fn struct_body.rec() { ${struct_body.rec()} }
+//!
+//!     macro rec($s:struct) { ${s.body.rec()} }
+//! }
+//!
+//! #[inception::attr(rec)]
+//! struct Foo {
+//!     a: u8,
+//! }
+//! ```
+//!
+//! Concatenations that aren't valid identifiers:
+//!
+//! ```compile_fail
+//! inception::rules! {
+//!     macro number($a:ident) { let ${concat("1", a)} = 0; }
+//! }
+//!
+//! fn main() {
+//!     number!(x);
+//! }
+//! ```
+//!
+//! Alternatives that are shadowed by an earlier alternative:
+//!
+//! ```compile_fail
+//! inception::rules! {
+//!     match item as Any($tt) | Foo(foo);
+//! }
+//! ```
+//!
+//! Patterns that backtrack too much exceed the step budget:
+//!
+//! ```compile_fail
+//! inception::rules! {
+//!     #![step_budget = 1000]
+//!
+//!     macro slow($a:tt* $b:tt* x) {}
+//! }
+//!
+//! slow! {
+//!     a a a a a a a a a a a a a a a a a a a a
+//!     a a a a a a a a a a a a a a a a a a a a
+//!     a a a a a a a a a a a a a a a a a a a a
+//!     x
+//! }
+//! ```
+//!
+//! Fragments with unexpected tokens in a nested group:
+//!
+//! ```compile_fail
+//! inception::rules! {
+//!     macro one_attr($a:attr) {}
+//! }
+//!
+//! one_attr!(#[a b]);
+//! ```
+//!
+//! Counting a capture that doesn't exist:
+//!
+//! ```compile_fail
+//! inception::rules! {
+//!     macro len($items:tt*) { ${count(item)} }
+//! }
+//!
+//! const N: usize = len!(a b c);
+//! ```
+//!
+//! `$match` arms that bind captures the alternative doesn't have:
+//!
+//! ```compile_fail
+//! inception::rules! {
+//!     match shape as Circle(circle $radius:literal) | Square(square $side:literal);
+//!
+//!     macro area($s:shape) {
+//!         $match s {
+//!             Circle { radius } => { 3 * $radius * $radius }
+//!             Square { size, .. } => { $size * $size }
+//!         }
+//!     }
+//! }
+//!
+//! const AREA: i32 = area!(square 2);
+//! ```
+//!
+//! Comparisons with captures that don't exist:
+//!
+//! ```compile_fail
+//! inception::rules! {
+//!     macro is_unit($s:ident) { $if $name == Unit { true } $else { false } }
+//! }
+//!
+//! const UNIT: bool = is_unit!(Unit);
+//! ```
+//!
+//! Matchers that are provided by more than one glob import:
+//!
+//! ```compile_fail
+//! mod a {
+//!     inception::rules! {
+//!         pub match kv as ($key:ident = $value:literal);
+//!     }
+//! }
+//!
+//! mod b {
+//!     inception::rules! {
+//!         pub match kv as ($key:ident : $value:literal);
+//!     }
+//! }
+//!
+//! inception::rules! {
+//!     use crate::a::*;
+//!     use crate::b::*;
+//!
+//!     macro key($kv) { stringify!(${kv.key}) }
+//! }
+//!
+//! fn main() {}
+//! ```
@@ -0,0 +1,9 @@
+//! A library of `match` rules that is used by `test-inception`
+
+pub mod dsl {
+    inception::rules! {
+        pub match rule as ($name:ident => $actions:{action , .. ,}?);
+        pub match action as ($verb:ident $target:target?);
+        match target as (( $expr ));
+    }
+}
@@ -1,50 +1,514 @@
-inception::rules! {
-    match struct as (/* $meta* */ $vis struct $name:ident $generics:generic_params? $struct_rest);
+// the generated items are only used by the tests
+#![cfg_attr(not(test), allow(dead_code))]
 
-    match vis as (pub($vis_inner)) | (pub) | ();
-    match vis_inner as (crate) | (self) | (super) | (in $path);
-    match path as ();
+inception::rules! {
+    match struct as ($meta:attr* $vis struct $name:ident $generics:generic_params? $struct_rest);
 
     match generic_params as (< ${generic_param , .. ,}? >);
     match generic_param /*..*/ as
-        | (/* $meta* */ $ident $colon_type_bounds?)
-        | (/* $meta* */ $lifetime $colon_lifetime_bounds?)
-        | (/* $meta* */ const $ident $: $ty);
+        | Type($meta:attr* $ident $colon_type_bounds?)
+        | Lifetime($meta:attr* $lifetime $colon_lifetime_bounds?)
+        | Const($meta:attr* const $ident $: $ty);
     match colon_type_bounds /*..*/ as (: $bounds:{bound + ..}?);
     match colon_lifetime_bounds /*..*/ as (: $bounds:{lifetime + ..}?);
     match bound as ($lifetime) | ($ty);
 
     match where_clause as (where $where_bounds*);
     match where_bounds /*..*/ as
-        | (/* $meta* */ $ty $colon_type_bounds)
-        | (/* $meta* */ $lifetime $colon_lifetime_bounds);
+        | ($meta:attr* $ty $colon_type_bounds)
+        | ($meta:attr* $lifetime $colon_lifetime_bounds);
 
     match struct_rest /*..*/ as
-        | ($where_clause? $body:struct_body)
-        | ($body:tuple_struct_body? $where_clause? ;);
+        | Regular($where_clause? $body:struct_body)
+        | Tuple($body:tuple_struct_body? $where_clause? ;);
 
     match struct_body as ({ $fields:{struct_field , .. ,}? });
     match tuple_struct_body as (( $fields:{tuple_struct_field , .. ,}? ));
-    match struct_field as (/* $meta* */ $vis $ident $: $ty);
-    match tuple_struct_field as ($meta* $vis $ty);
+    match struct_field as ($meta:attr* $vis $ident $: $ty);
+    match tuple_struct_field as ($meta:attr* $vis $ty);
 
     pub macro Foo1($s: struct) {
         /// This struct was parsed and expanded again by inception!
         ${s.meta}
         ${s.vis} struct ${s.name} ${s.generics}
-        $if s.is_regular_struct {
-            ${s.where_clause} ${s.struct_rest.body}
+        $if s.struct_rest.Regular {
+            ${s.struct_rest.where_clause} ${s.struct_rest.body}
         } $else {
-            ${s.struct_rest.body} ${s.where_clause};
+            ${s.struct_rest.body} ${s.struct_rest.where_clause};
+        }
+    }
+}
+
+inception::rules! {
+    macro assert_all(${expr , .. ,}) {
+        $for e in expr {
+            assert!($e);
+        }
+    }
+}
+
+inception::rules! {
+    macro is_match($value:expr, $pattern:pat) {
+        match $value {
+            $pattern => true,
+            _ => false,
         }
     }
 }
 
+inception::rules! {
+    macro first_alternative($p:pat_param | $rest:pat) {
+        stringify!($p)
+    }
+}
+
+inception::rules! {
+    macro block_value($b:block) {
+        $b
+    }
+}
+
+inception::rules! {
+    macro meta_str($m:meta) {
+        stringify!($m).replace(' ', "")
+    }
+}
+
+inception::rules! {
+    macro attr_strs($attrs:attr*) {
+        [$for a in attrs { stringify!($a).replace(' ', ""), }]
+    }
+}
+
+inception::rules! {
+    macro path_str($p:path) {
+        stringify!($p).replace(' ', "")
+    }
+}
+
+inception::rules! {
+    macro with_stmts($items:item* $stmts:{stmt ; ..} => $result:expr) {
+        {
+            $items
+            $for s in stmts {
+                $s;
+            }
+            $result
+        }
+    }
+}
+
+inception::rules! {
+    #![step_budget = 1000]
+
+    macro last_after_semicolon($a:tt* ; $b:tt) {
+        $b
+    }
+}
+
+inception::rules! {
+    macro literals($s:str, $bs:byte_str, $n:int, $f:float, $c:char, $b:byte, $t:bool) {
+        ($s, $bs, $n, $f, $c, $b, $t)
+    }
+
+    macro cases($name:ident) {
+        [
+            stringify!(${snake_case(name)}),
+            stringify!(${camel_case(name)}),
+            stringify!(${pascal_case(name)}),
+            stringify!(${shouty_snake_case(name)}),
+            ${kebab_case(name)},
+        ]
+    }
+
+    macro same_idents($a:ident $b:ident?) {
+        $if $a == $b { true } $else { false }
+    }
+
+    macro is_two($b:tt) {
+        $if $b == 2 || $b == "two" { true } $else { false }
+    }
+
+    macro concat_pieces($a:ident $b:tt) {
+        {
+            let ${concat(a, "_", b)} = ${concat(a, "::", b)};
+            ${concat(a, "_", b)}
+        }
+    }
+
+    macro concat_raw($a:ident) {
+        (stringify!(${concat(a, "pe")}), ${concat(a, "²")})
+    }
+}
+
 #[inception::attr(Foo1)]
-pub(crate) struct Bar {}
+#[derive(Debug, Default)]
+pub(crate) struct Bar {
+    /// Doc comments are attributes, too
+    #[allow(unused)]
+    a: Vec<u8>,
+    b: (&'static str, [u8; 4]),
+    pub(crate) c: Option<<u8 as std::ops::Add>::Output>,
+}
+
+inception::rules! {
+    #![deny_ambiguity]
+    use rust_parser::v2;
+
+    fn struct_body.names() {
+        $for name in struct_body.fields.name { stringify!($name), }
+    }
+
+    macro struct_body.qualified($prefix:ident) {
+        $for name in struct_body.fields.name { concat!(stringify!($prefix), "::", stringify!($name)), }
+    }
+
+    macro variant_names($e:enum) {
+        $e
+
+        impl ${e.name} {
+            const VARIANTS: &[&str] = &[$for name in e.body.variants.name { stringify!($name), }];
+            const VARIANT_FIELDS: &[&[&str]] = &[$for v in e.body.variants {
+                &[$for ty in v.body.fields.ty { stringify!($ty), }],
+            }];
+        }
+    }
+
+    macro field_names($s:struct) {
+        $s
+
+        const FIELD_NAMES: &[&str] = &[$for name in s.body.fields.name { stringify!($name), }];
+        const QUALIFIED_FIELD_NAMES: &[&str] = &[${s.body.qualified(${s.name})}];
+        const FIELD_LIST: &str = concat!($for name, i in s.body.fields.name {
+            stringify!($name), $if !i.is_last() { ", ", }
+        });
+        const FIRST_FIELD: &str = $for name, i in s.body.fields.name {
+            $if i.is_first() { stringify!($name) }
+        };
+        const FIELD_TYPES: &[(&str, &str)] =
+            &[$for (name, ty) in zip(s.body.fields.name, s.body.fields.ty) {
+                (stringify!($name), stringify!($ty)),
+            }];
+        const LAST_FIELD: &str = $for field, i in s.body.fields {
+            $if i.is_last() { stringify!(${field.name}) }
+        };
+        const PUBLIC_FIELDS: &[&str] = &[$for field in s.body.fields {
+            $if !field.vis.is_empty() { stringify!(${field.name}), }
+        }];
+        const LAST_FIELD_TYPE: &str = stringify!(${last(s.body.fields.ty)});
+        const FIELD_POSITIONS: &[(usize, usize)] = &[$for name, i in s.body.fields.name {
+            ($i, ${i.len()}),
+        }];
+    }
+
+    macro struct_kind($s:struct) {
+        $s
+
+        impl ${s.name} {
+            const KIND: &str = $match s.body {
+                struct_body { fields } => {
+                    concat!("named", $for name in fields.name { " ", stringify!($name), })
+                }
+                tuple_struct_body { .. } => { "tuple" }
+                _ => { "unit" }
+            };
+            const NAMES: &[&str] = &[$if s.Named { ${s.body.names()} }];
+            const IS_UNIT: bool = $if s.Unit { true } $else { false };
+            const HAS_FIELDS: bool =
+                $if (s.Named || s.Tuple) && !s.body.fields.is_empty() { true } $else { false };
+            const IS_PUBLIC: bool = $if s.vis.is_empty() { false } $else { true };
+            const IS_NAMED_STRUCT: bool = $if $s.name == Named && $s.name != Unit { true } $else { false };
+            const FIELD_COUNT: usize = $match s {
+                Named { body, .. } => { ${count(body.fields)} }
+                Tuple { body, .. } => { ${count(body.fields)} }
+                Unit => { 0 }
+            };
+        }
+    }
+}
+
+#[inception::attr(struct_kind)]
+#[allow(dead_code)]
+struct Named {
+    a: u8,
+    b: u8,
+}
+
+#[inception::attr(struct_kind)]
+#[allow(dead_code)]
+struct Tuple(u8);
+
+#[inception::attr(struct_kind)]
+pub(crate) struct Unit;
+
+#[inception::attr(variant_names)]
+#[allow(dead_code)]
+#[repr(u8)]
+enum Color {
+    Red,
+    Green = 2,
+    Blue(u8),
+    Custom { r: u8, g: u8, b: u8 },
+}
+
+#[inception::attr(field_names)]
+#[allow(dead_code)]
+pub(crate) struct Wrapper<'a, T: Clone + 'a, const N: usize = 3>
+where
+    T: Default,
+{
+    inner: [&'a T; N],
+    pub count: usize,
+}
+
+mod point {
+    #[inception::attr(Foo1)]
+    pub(crate) struct Point {
+        pub(in crate::point) x: i32,
+        pub y: i32,
+    }
+
+    #[inception::attr(Foo1)]
+    pub(crate) struct Pair(pub i32, pub i32);
+
+    pub(crate) fn origin() -> Point {
+        let point = Point { x: 0, y: 0 };
+        Point { x: point.x, ..point }
+    }
+}
+
+mod settings {
+    inception::rules! {
+        pub match setting as ($key:ident $value:setting_value);
+        match setting_value as (= $expr);
+
+        pub macro double($e:expr) {
+            2 * ($e)
+        }
+    }
+}
+
+mod pairs_a {
+    inception::rules! {
+        pub match kv as ($key:ident = $value:literal);
+    }
+}
+
+mod pairs_b {
+    inception::rules! {
+        pub match kv as ($key:ident = $value:literal);
+    }
+}
+
+inception::rules! {
+    use crate::pairs_a::kv;
+
+    macro key_a($kv) {
+        stringify!(${kv.key})
+    }
+}
+
+inception::rules! {
+    use crate::pairs_b::kv;
+
+    macro value_b($kv) {
+        ${kv.value}
+    }
+}
+
+mod shapes {
+    inception::rules! {
+        pub match point as (($x:literal, $y:literal));
+    }
+
+    inception::rules! {
+        pub match size as ($w:literal x $h:literal);
+    }
+}
+
+inception::rules! {
+    use crate::shapes::*;
+
+    macro corner($point $size) {
+        (${point.x} + ${size.w}, ${point.y} + ${size.h})
+    }
+}
+
+inception::rules! {
+    use crate::shapes::*;
+    use crate::settings::*;
+
+    macro point_setting($point $setting) {
+        (${point.x}, stringify!(${setting.key}))
+    }
+}
+
+inception::rules! {
+    use crate::settings::setting;
+
+    macro setting_keys($settings:{setting , .. ,}?) {
+        [$for key in settings.key { stringify!($key), }]
+    }
+}
+
+inception::rules! {
+    use test_grammar::dsl::*;
+
+    macro rule_names($rules:{rule ; .. ;}?) {
+        [$for name in rules.name { stringify!($name), }]
+    }
+
+    macro verbs($action) {
+        stringify!(${action.verb})
+    }
+}
+
+inception::rules! {
+    match old_expr as Expr($e:expr_2021) | Other($tt*);
+
+    macro expr_kind($x:old_expr) {
+        $if x.Expr { "expr" } $else { "other" }
+    }
+}
+
+inception::rules! {
+    use rust_parser::v1;
+
+    macro bounded_v1($s:struct) {
+        [$for bounds in s.generics.params.bounds { stringify!($bounds), }]
+    }
+}
+
+inception::rules! {
+    use rust_parser::v2;
+
+    macro bounded_v2($s:struct) {
+        [$for bounds in s.generics.params.bounds { stringify!($bounds), }]
+    }
+}
+
+fn main() {}
+
+#[test]
+fn expressions() {
+    let bar = Bar { a: vec![], b: ("", [0; 4]), c: None };
+    assert_all!(
+        bar.a.is_empty() && bar.b.0.is_empty(),
+        [1, 2].iter().map(|x| x * 2).sum::<i32>() == 6 || bar.a.len() > 1,
+        match bar.b.1 {
+            [0, ..] => 1..2,
+            _ => 0..0,
+        }
+        .contains(&1),
+        if bar.a.len() < 2 { 1 } else { 2 } as u8 == 1,
+        bar.c.is_none(),
+        format!("{:?}", Bar::default()).starts_with("Bar {"),
+        point::origin().y == 0,
+        last_after_semicolon!(1 2 ; 3 ; (bar.c)).is_none(),
+        is_match!(bar.b.1, [1, ..] | [0, 0, ..]),
+    );
+}
+
+#[test]
+fn fragments() {
+    assert_all!(
+        literals!("s", b"bs", -1, -2.5, 'c', b'b', true) == ("s", b"bs", -1, -2.5, 'c', b'b', true),
+        expr_kind!(1 + 2) == "expr",
+        expr_kind!(_) == "other",
+        expr_kind!(const { 1 }) == "other",
+        path_str!(Vec::<u8>::new) == "Vec::<u8>::new",
+        path_str!(::std::mem::swap) == "::std::mem::swap",
+        path_str!(<T as Iterator>::Item) == "<TasIterator>::Item",
+        path_str!(Fn(u8) -> u8) == "Fn(u8)->u8",
+        path_str!(a::Fn(u8)::b) == "a::Fn(u8)::b",
+        first_alternative!(Some(1 | 2) | None) == "Some(1 | 2)",
+        block_value!({
+            let x = 2;
+            x * 3
+        }) == 6,
+        meta_str!(doc = "x") == "doc=\"x\"",
+        meta_str!(unsafe(no_mangle)) == "unsafe(no_mangle)",
+        attr_strs!(#![allow(unused)] #[inline] #[unsafe(export_name = "f")])
+            == ["#![allow(unused)]", "#[inline]", "#[unsafe(export_name=\"f\")]"],
+        with_stmts!(const TWO: u8 = 2; let x = TWO; let y = x * 2 => y == 4),
+        with_stmts!(let Some(x) = Some(3) else { panic!() }; let y = x + 1 => y == 4),
+    );
+}
+
+#[test]
+fn grammar() {
+    assert_all!(
+        Color::VARIANTS == ["Red", "Green", "Blue", "Custom"],
+        Color::VARIANT_FIELDS == [&[][..], &[], &["u8"], &["u8", "u8", "u8"]],
+        FIELD_NAMES == ["inner", "count"],
+        QUALIFIED_FIELD_NAMES == ["Wrapper::inner", "Wrapper::count"],
+        PUBLIC_FIELDS == ["count"] && LAST_FIELD_TYPE == "usize",
+        Wrapper::<u8, 0> { inner: [], count: 0 }.count == 0,
+        [Named::KIND, Tuple::KIND, Unit::KIND] == ["named a b", "tuple", "unit"],
+        Named::NAMES == ["a", "b"] && Tuple::NAMES.is_empty() && Unit::NAMES.is_empty(),
+        [Named::IS_UNIT, Tuple::IS_UNIT, Unit::IS_UNIT] == [false, false, true],
+        [Named::HAS_FIELDS, Tuple::HAS_FIELDS, Unit::HAS_FIELDS] == [true, true, false],
+        [Named::IS_PUBLIC, Tuple::IS_PUBLIC, Unit::IS_PUBLIC] == [false, false, true],
+        [Named::IS_NAMED_STRUCT, Tuple::IS_NAMED_STRUCT, Unit::IS_NAMED_STRUCT]
+            == [true, false, false],
+        [Named::FIELD_COUNT, Tuple::FIELD_COUNT, Unit::FIELD_COUNT] == [2, 1, 0],
+        point::Pair(1, 2).0 + point::Pair(1, 2).1 == 3,
+        bounded_v1!(
+            struct S<F: Fn(u8) -> u8>(F);
+        ) == [": Fn(u8) -> u8"],
+        bounded_v1!(
+            struct S<F: for<'b> Fn(&'b u8) + Copy>(F);
+        ) == [": for < 'b > Fn(&'b u8) + Copy"],
+        bounded_v2!(
+            struct S<F: Fn(u8) -> u8>(F);
+        ) == [": Fn(u8) -> u8"],
+        bounded_v2!(
+            struct S<F: for<'b> Fn(&'b u8) + Copy>(F);
+        ) == [": for < 'b > Fn(&'b u8) + Copy"],
+    );
+}
+
+#[test]
+fn for_loops() {
+    assert_all!(
+        FIELD_LIST == "inner, count",
+        FIRST_FIELD == "inner",
+        LAST_FIELD == "count",
+        FIELD_POSITIONS == [(0, 2), (1, 2)],
+        FIELD_TYPES == [("inner", "[&'a T; N]"), ("count", "usize")],
+    );
+}
+
+#[test]
+fn functions() {
+    assert_all!(
+        concat_pieces!(foo 2) == "foo::2",
+        concat_raw!(ty) == ("r#type", "ty²"),
+        cases!(HTTPServer_v2)
+            == [
+                "http_server_v2",
+                "httpServerV2",
+                "HttpServerV2",
+                "HTTP_SERVER_V2",
+                "http-server-v2"
+            ],
+        cases!(Type)[0] == "r#type",
+        [is_two!(2), is_two!("two"), is_two!(3)] == [true, true, false],
+        [same_idents!(x x), same_idents!(b)] == [true, false],
+    );
+}
 
-fn main() {
-    let _bar = Bar {};
+#[test]
+fn imports() {
+    assert_all!(
+        [key_a!(x = 1), value_b!(y = "v")] == ["x", "v"],
+        corner!((1, 2) 3 x 4) == (4, 6),
+        point_setting!((1, 2) width = 3) == (1, "width"),
+        setting_keys!(width = 2, height = 3 * 4,) == ["width", "height"],
+        settings::double!(21) == 42,
+        rule_names!(start => open(door), walk; stop => ;) == ["start", "stop"],
+        verbs!(jump) == "jump",
+    );
 }
 
 /*
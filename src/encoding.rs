@@ -0,0 +1,838 @@
+//! Token-based serialization of compiled [`Rules`], which is embedded in the generated
+//! `macro_rules!` forwarder and decoded by `inception::expand!`.
+//!
+//! The format is a sequence of tokens that never contains `$`, so it can appear in the
+//! transcriber of a `macro_rules!` macro:
+//!
+//! - strings are string literals, integers are unsuffixed integer literals, booleans are
+//!   `true` or `false`, characters are char literals
+//! - sequences and optional values are bracketed: `[a b c]`, `[]`
+//! - maps are bracketed sequences of key-value pairs, sorted by key
+//! - structs and tuples are their fields in declaration order
+//! - enums are a lowercase tag followed by the fields of the variant
+//!
+//! The encoded rules start with [`FORMAT_VERSION`], which must be incremented whenever the
+//! format changes.
+
+use std::collections::HashMap;
+
+use proc_macro2::{Delimiter, Group, Literal, Spacing, Span, TokenStream, TokenTree};
+use syn::{ext::IdentExt, parse::ParseStream};
+
+use crate::macros::{
+    Alternative, Delimiter as GroupDelimiter, FnRule, Literal as MacroLiteral, MacroRule, Options,
+    Path, Punct, Rules, Vis,
+    pattern::{
+        Interspersed, Pattern, PatternGroup, PatternMatcher, Patterns, Quantifier, Repeat,
+        RepeatKind,
+    },
+    replacement::{
+        Condition, Expr, ForExpr, MatchArm, MatchBinding, Operand, Replacement, ReplacementGroup,
+        SpecialReplacement,
+    },
+};
+
+const FORMAT_VERSION: usize = 8;
+
+/// Encodes the rules for the forwarder of the macro `name`. The other macros of the `rules!` block
+/// are omitted, since they can't be invoked through this forwarder.
+pub(crate) fn encode_rules(rules: &Rules, name: &str) -> TokenStream {
+    let Rules { macros, functions, matches, options } = rules;
+    let macros = macros.iter().filter(|m| m.name == name).collect::<Vec<_>>();
+
+    let mut out = Vec::new();
+    FORMAT_VERSION.encode(&mut out);
+    macros.encode(&mut out);
+    functions.encode(&mut out);
+    matches.encode(&mut out);
+    options.encode(&mut out);
+    out.into_iter().collect()
+}
+
+pub(crate) fn decode_rules(input: ParseStream) -> syn::Result<Rules> {
+    decode_versioned(input)
+}
+
+/// Encodes the matchers exported by a `pub match`
+pub(crate) fn encode_matches(matches: &HashMap<String, Box<[Alternative]>>) -> TokenStream {
+    let mut out = Vec::new();
+    FORMAT_VERSION.encode(&mut out);
+    matches.encode(&mut out);
+    out.into_iter().collect()
+}
+
+pub(crate) fn decode_matches(
+    input: ParseStream,
+) -> syn::Result<HashMap<String, Box<[Alternative]>>> {
+    decode_versioned(input)
+}
+
+fn decode_versioned<T: Decode>(input: ParseStream) -> syn::Result<T> {
+    let version_span = input.span();
+    let version = usize::decode(input)?;
+    if version != FORMAT_VERSION {
+        synerr!(
+            version_span,
+            "rules were compiled with encoding version {version}, expected {FORMAT_VERSION}; \
+            make sure that all crates use the same version of inception"
+        );
+    }
+    let value = T::decode(input)?;
+    if !input.is_empty() {
+        synerr!(input.span(), "unexpected tokens after the encoded rules");
+    }
+    Ok(value)
+}
+
+pub(crate) trait Encode {
+    fn encode(&self, out: &mut Vec<TokenTree>);
+}
+
+pub(crate) trait Decode: Sized {
+    fn decode(input: ParseStream) -> syn::Result<Self>;
+}
+
+fn tag(name: &str) -> TokenTree {
+    TokenTree::Ident(proc_macro2::Ident::new(name, Span::call_site()))
+}
+
+fn decode_tag(input: ParseStream) -> syn::Result<(String, Span)> {
+    let ident = input.call(syn::Ident::parse_any)?;
+    Ok((ident.to_string(), ident.span()))
+}
+
+fn bracketed(content: Vec<TokenTree>) -> TokenTree {
+    TokenTree::Group(Group::new(Delimiter::Bracket, content.into_iter().collect()))
+}
+
+fn decode_bracketed<T>(
+    input: ParseStream,
+    mut f: impl FnMut(ParseStream) -> syn::Result<T>,
+) -> syn::Result<Vec<T>> {
+    let content;
+    syn::bracketed!(content in input);
+    let mut result = Vec::new();
+    while !content.is_empty() {
+        result.push(f(&content)?);
+    }
+    Ok(result)
+}
+
+impl Encode for String {
+    fn encode(&self, out: &mut Vec<TokenTree>) {
+        out.push(TokenTree::Literal(Literal::string(self)));
+    }
+}
+
+impl Decode for String {
+    fn decode(input: ParseStream) -> syn::Result<Self> {
+        Ok(input.parse::<syn::LitStr>()?.value())
+    }
+}
+
+impl Encode for usize {
+    fn encode(&self, out: &mut Vec<TokenTree>) {
+        out.push(TokenTree::Literal(Literal::usize_unsuffixed(*self)));
+    }
+}
+
+impl Decode for usize {
+    fn decode(input: ParseStream) -> syn::Result<Self> {
+        input.parse::<syn::LitInt>()?.base10_parse()
+    }
+}
+
+impl Encode for bool {
+    fn encode(&self, out: &mut Vec<TokenTree>) {
+        out.push(tag(if *self { "true" } else { "false" }));
+    }
+}
+
+impl Decode for bool {
+    fn decode(input: ParseStream) -> syn::Result<Self> {
+        Ok(input.parse::<syn::LitBool>()?.value)
+    }
+}
+
+impl<T: Encode + ?Sized> Encode for &T {
+    fn encode(&self, out: &mut Vec<TokenTree>) {
+        (**self).encode(out);
+    }
+}
+
+impl<T: Encode> Encode for Option<T> {
+    fn encode(&self, out: &mut Vec<TokenTree>) {
+        let mut content = Vec::new();
+        if let Some(value) = self {
+            value.encode(&mut content);
+        }
+        out.push(bracketed(content));
+    }
+}
+
+impl<T: Decode> Decode for Option<T> {
+    fn decode(input: ParseStream) -> syn::Result<Self> {
+        let content;
+        syn::bracketed!(content in input);
+        if content.is_empty() { Ok(None) } else { T::decode(&content).map(Some) }
+    }
+}
+
+impl<T: Encode> Encode for [T] {
+    fn encode(&self, out: &mut Vec<TokenTree>) {
+        let mut content = Vec::new();
+        for item in self {
+            item.encode(&mut content);
+        }
+        out.push(bracketed(content));
+    }
+}
+
+impl<T: Encode> Encode for Vec<T> {
+    fn encode(&self, out: &mut Vec<TokenTree>) {
+        (**self).encode(out);
+    }
+}
+
+impl<T: Encode> Encode for Box<[T]> {
+    fn encode(&self, out: &mut Vec<TokenTree>) {
+        (**self).encode(out);
+    }
+}
+
+impl<T: Decode> Decode for Box<[T]> {
+    fn decode(input: ParseStream) -> syn::Result<Self> {
+        decode_bracketed(input, T::decode).map(Vec::into_boxed_slice)
+    }
+}
+
+impl<A: Encode, B: Encode> Encode for (A, B) {
+    fn encode(&self, out: &mut Vec<TokenTree>) {
+        self.0.encode(out);
+        self.1.encode(out);
+    }
+}
+
+impl<A: Decode, B: Decode> Decode for (A, B) {
+    fn decode(input: ParseStream) -> syn::Result<Self> {
+        Ok((A::decode(input)?, B::decode(input)?))
+    }
+}
+
+impl<T: Encode> Encode for HashMap<String, T> {
+    fn encode(&self, out: &mut Vec<TokenTree>) {
+        let mut entries = self.iter().collect::<Vec<_>>();
+        entries.sort_by_key(|&(key, _)| key);
+
+        let mut content = Vec::new();
+        for (key, value) in entries {
+            key.encode(&mut content);
+            value.encode(&mut content);
+        }
+        out.push(bracketed(content));
+    }
+}
+
+impl<T: Decode> Decode for HashMap<String, T> {
+    fn decode(input: ParseStream) -> syn::Result<Self> {
+        let entries = decode_bracketed(input, |input| {
+            let key = String::decode(input)?;
+            let value = T::decode(input)?;
+            Ok((key, value))
+        })?;
+        Ok(entries.into_iter().collect())
+    }
+}
+
+impl Decode for Rules {
+    fn decode(input: ParseStream) -> syn::Result<Self> {
+        Ok(Rules {
+            macros: Decode::decode(input)?,
+            functions: Decode::decode(input)?,
+            matches: Decode::decode(input)?,
+            options: Decode::decode(input)?,
+        })
+    }
+}
+
+impl Encode for Options {
+    fn encode(&self, out: &mut Vec<TokenTree>) {
+        let Options { step_budget, deny_ambiguity } = self;
+        step_budget.encode(out);
+        deny_ambiguity.encode(out);
+    }
+}
+
+impl Decode for Options {
+    fn decode(input: ParseStream) -> syn::Result<Self> {
+        Ok(Options { step_budget: Decode::decode(input)?, deny_ambiguity: Decode::decode(input)? })
+    }
+}
+
+impl Encode for MacroRule {
+    fn encode(&self, out: &mut Vec<TokenTree>) {
+        let MacroRule { vis, name, patterns, replacements } = self;
+        vis.encode(out);
+        name.encode(out);
+        patterns.encode(out);
+        replacements.encode(out);
+    }
+}
+
+impl Decode for MacroRule {
+    fn decode(input: ParseStream) -> syn::Result<Self> {
+        Ok(MacroRule {
+            vis: Decode::decode(input)?,
+            name: Decode::decode(input)?,
+            patterns: Decode::decode(input)?,
+            replacements: Decode::decode(input)?,
+        })
+    }
+}
+
+impl Encode for Vis {
+    fn encode(&self, out: &mut Vec<TokenTree>) {
+        out.push(tag(match self {
+            Vis::Private => "private",
+            Vis::Public => "public",
+        }));
+    }
+}
+
+impl Decode for Vis {
+    fn decode(input: ParseStream) -> syn::Result<Self> {
+        let (tag, span) = decode_tag(input)?;
+        Ok(match tag.as_str() {
+            "private" => Vis::Private,
+            "public" => Vis::Public,
+            _ => synerr!(span, "unknown visibility `{tag}`"),
+        })
+    }
+}
+
+impl Encode for FnRule {
+    fn encode(&self, out: &mut Vec<TokenTree>) {
+        let FnRule { matcher, name, params, body } = self;
+        matcher.encode(out);
+        name.encode(out);
+        params.encode(out);
+        body.encode(out);
+    }
+}
+
+impl Decode for FnRule {
+    fn decode(input: ParseStream) -> syn::Result<Self> {
+        Ok(FnRule {
+            matcher: Decode::decode(input)?,
+            name: Decode::decode(input)?,
+            params: Decode::decode(input)?,
+            body: Decode::decode(input)?,
+        })
+    }
+}
+
+impl Encode for Alternative {
+    fn encode(&self, out: &mut Vec<TokenTree>) {
+        let Alternative { label, patterns, span: _ } = self;
+        label.encode(out);
+        patterns.encode(out);
+    }
+}
+
+impl Decode for Alternative {
+    fn decode(input: ParseStream) -> syn::Result<Self> {
+        Ok(Alternative {
+            label: Decode::decode(input)?,
+            patterns: Decode::decode(input)?,
+            span: Span::call_site(),
+        })
+    }
+}
+
+impl Encode for Patterns {
+    fn encode(&self, out: &mut Vec<TokenTree>) {
+        self.0.encode(out);
+    }
+}
+
+impl Decode for Patterns {
+    fn decode(input: ParseStream) -> syn::Result<Self> {
+        Decode::decode(input).map(Patterns)
+    }
+}
+
+impl Encode for Pattern {
+    fn encode(&self, out: &mut Vec<TokenTree>) {
+        match self {
+            Pattern::Group(PatternGroup { delimiter, content }) => {
+                out.push(tag("group"));
+                delimiter.encode(out);
+                content.encode(out);
+            }
+            Pattern::Ident(ident) => {
+                out.push(tag("ident"));
+                ident.encode(out);
+            }
+            Pattern::Punct(punct) => {
+                out.push(tag("punct"));
+                punct.encode(out);
+            }
+            Pattern::Literal(lit) => {
+                out.push(tag("literal"));
+                lit.encode(out);
+            }
+            Pattern::Matcher(PatternMatcher { name, ty, repeat }) => {
+                out.push(tag("matcher"));
+                name.encode(out);
+                ty.encode(out);
+                repeat.encode(out);
+            }
+        }
+    }
+}
+
+impl Decode for Pattern {
+    fn decode(input: ParseStream) -> syn::Result<Self> {
+        let (tag, span) = decode_tag(input)?;
+        Ok(match tag.as_str() {
+            "group" => Pattern::Group(PatternGroup {
+                delimiter: Decode::decode(input)?,
+                content: Decode::decode(input)?,
+            }),
+            "ident" => Pattern::Ident(Decode::decode(input)?),
+            "punct" => Pattern::Punct(Decode::decode(input)?),
+            "literal" => Pattern::Literal(Decode::decode(input)?),
+            "matcher" => Pattern::Matcher(PatternMatcher {
+                name: Decode::decode(input)?,
+                ty: Decode::decode(input)?,
+                repeat: Decode::decode(input)?,
+            }),
+            _ => synerr!(span, "unknown pattern kind `{tag}`"),
+        })
+    }
+}
+
+impl Encode for Repeat {
+    fn encode(&self, out: &mut Vec<TokenTree>) {
+        let Repeat { quantifier, interspersed } = self;
+        out.push(tag(match quantifier {
+            Quantifier::Star => "star",
+            Quantifier::Plus => "plus",
+            Quantifier::QuestionMark => "question_mark",
+        }));
+        interspersed.encode(out);
+    }
+}
+
+impl Decode for Repeat {
+    fn decode(input: ParseStream) -> syn::Result<Self> {
+        let (tag, span) = decode_tag(input)?;
+        let quantifier = match tag.as_str() {
+            "star" => Quantifier::Star,
+            "plus" => Quantifier::Plus,
+            "question_mark" => Quantifier::QuestionMark,
+            _ => synerr!(span, "unknown quantifier `{tag}`"),
+        };
+        Ok(Repeat { quantifier, interspersed: Decode::decode(input)? })
+    }
+}
+
+impl Encode for Interspersed {
+    fn encode(&self, out: &mut Vec<TokenTree>) {
+        let Interspersed { kind, punct } = self;
+        out.push(tag(match kind {
+            RepeatKind::Leading => "leading",
+            RepeatKind::Trailing => "trailing",
+            RepeatKind::Middle => "middle",
+        }));
+        punct.encode(out);
+    }
+}
+
+impl Decode for Interspersed {
+    fn decode(input: ParseStream) -> syn::Result<Self> {
+        let (tag, span) = decode_tag(input)?;
+        let kind = match tag.as_str() {
+            "leading" => RepeatKind::Leading,
+            "trailing" => RepeatKind::Trailing,
+            "middle" => RepeatKind::Middle,
+            _ => synerr!(span, "unknown repetition kind `{tag}`"),
+        };
+        Ok(Interspersed { kind, punct: Decode::decode(input)? })
+    }
+}
+
+impl Encode for Replacement {
+    fn encode(&self, out: &mut Vec<TokenTree>) {
+        match self {
+            Replacement::Group(ReplacementGroup { delimiter, content }) => {
+                out.push(tag("group"));
+                delimiter.encode(out);
+                content.encode(out);
+            }
+            Replacement::Ident(ident) => {
+                out.push(tag("ident"));
+                ident.encode(out);
+            }
+            Replacement::Punct(punct) => {
+                out.push(tag("punct"));
+                punct.encode(out);
+            }
+            Replacement::Literal(lit) => {
+                out.push(tag("literal"));
+                lit.encode(out);
+            }
+            Replacement::Special(special) => special.encode(out),
+        }
+    }
+}
+
+impl Decode for Replacement {
+    fn decode(input: ParseStream) -> syn::Result<Self> {
+        let (tag, span) = decode_tag(input)?;
+        Ok(match tag.as_str() {
+            "group" => Replacement::Group(ReplacementGroup {
+                delimiter: Decode::decode(input)?,
+                content: Decode::decode(input)?,
+            }),
+            "ident" => Replacement::Ident(Decode::decode(input)?),
+            "punct" => Replacement::Punct(Decode::decode(input)?),
+            "literal" => Replacement::Literal(Decode::decode(input)?),
+            _ => Replacement::Special(decode_special(&tag, span, input)?),
+        })
+    }
+}
+
+impl Encode for SpecialReplacement {
+    fn encode(&self, out: &mut Vec<TokenTree>) {
+        match self {
+            SpecialReplacement::Path(path) => {
+                out.push(tag("path"));
+                path.encode(out);
+            }
+            SpecialReplacement::Call { func, args } => {
+                out.push(tag("call"));
+                func.encode(out);
+                args.encode(out);
+            }
+            SpecialReplacement::MethodCall { receiver, method, args } => {
+                out.push(tag("method_call"));
+                receiver.encode(out);
+                method.encode(out);
+                args.encode(out);
+            }
+            SpecialReplacement::If { condition, body } => {
+                out.push(tag("if"));
+                condition.encode(out);
+                body.encode(out);
+            }
+            SpecialReplacement::ElseIf { condition, body } => {
+                out.push(tag("else_if"));
+                condition.encode(out);
+                body.encode(out);
+            }
+            SpecialReplacement::Else { body } => {
+                out.push(tag("else"));
+                body.encode(out);
+            }
+            SpecialReplacement::For { bindings, index, expr, body } => {
+                out.push(tag("for"));
+                bindings.encode(out);
+                index.encode(out);
+                expr.encode(out);
+                body.encode(out);
+            }
+            SpecialReplacement::Match { expr, arms } => {
+                out.push(tag("match"));
+                expr.encode(out);
+                arms.encode(out);
+            }
+        }
+    }
+}
+
+fn decode_special(tag: &str, span: Span, input: ParseStream) -> syn::Result<SpecialReplacement> {
+    Ok(match tag {
+        "path" => SpecialReplacement::Path(Decode::decode(input)?),
+        "call" => {
+            SpecialReplacement::Call { func: Decode::decode(input)?, args: Decode::decode(input)? }
+        }
+        "method_call" => SpecialReplacement::MethodCall {
+            receiver: Decode::decode(input)?,
+            method: Decode::decode(input)?,
+            args: Decode::decode(input)?,
+        },
+        "if" => SpecialReplacement::If {
+            condition: Decode::decode(input)?,
+            body: Decode::decode(input)?,
+        },
+        "else_if" => SpecialReplacement::ElseIf {
+            condition: Decode::decode(input)?,
+            body: Decode::decode(input)?,
+        },
+        "else" => SpecialReplacement::Else { body: Decode::decode(input)? },
+        "for" => SpecialReplacement::For {
+            bindings: Decode::decode(input)?,
+            index: Decode::decode(input)?,
+            expr: Decode::decode(input)?,
+            body: Decode::decode(input)?,
+        },
+        "match" => {
+            SpecialReplacement::Match { expr: Decode::decode(input)?, arms: Decode::decode(input)? }
+        }
+        _ => synerr!(span, "unknown replacement kind `{tag}`"),
+    })
+}
+
+impl Encode for MatchArm {
+    fn encode(&self, out: &mut Vec<TokenTree>) {
+        let MatchArm { name, bindings, rest, body } = self;
+        name.encode(out);
+        bindings.encode(out);
+        rest.encode(out);
+        body.encode(out);
+    }
+}
+
+impl Decode for MatchArm {
+    fn decode(input: ParseStream) -> syn::Result<Self> {
+        Ok(MatchArm {
+            name: Decode::decode(input)?,
+            bindings: Decode::decode(input)?,
+            rest: Decode::decode(input)?,
+            body: Decode::decode(input)?,
+        })
+    }
+}
+
+impl Encode for MatchBinding {
+    // the span of the capture is kept, so `expand!` can point at bindings of unknown captures
+    fn encode(&self, out: &mut Vec<TokenTree>) {
+        let MatchBinding { capture, binding, span } = self;
+        let mut literal = Literal::string(capture);
+        literal.set_span(*span);
+        out.push(TokenTree::Literal(literal));
+        binding.encode(out);
+    }
+}
+
+impl Decode for MatchBinding {
+    fn decode(input: ParseStream) -> syn::Result<Self> {
+        let capture = input.parse::<syn::LitStr>()?;
+        Ok(MatchBinding {
+            capture: capture.value(),
+            binding: Decode::decode(input)?,
+            span: capture.span(),
+        })
+    }
+}
+
+impl Encode for Condition {
+    fn encode(&self, out: &mut Vec<TokenTree>) {
+        match self {
+            Condition::Capture(path) => {
+                out.push(tag("capture"));
+                path.encode(out);
+            }
+            Condition::Call { receiver, method } => {
+                out.push(tag("call"));
+                receiver.encode(out);
+                method.encode(out);
+            }
+            Condition::Eq(left, right) => {
+                out.push(tag("eq"));
+                left.encode(out);
+                right.encode(out);
+            }
+            Condition::Not(condition) => {
+                out.push(tag("not"));
+                condition.encode(out);
+            }
+            Condition::And(left, right) => {
+                out.push(tag("and"));
+                left.encode(out);
+                right.encode(out);
+            }
+            Condition::Or(left, right) => {
+                out.push(tag("or"));
+                left.encode(out);
+                right.encode(out);
+            }
+        }
+    }
+}
+
+impl Decode for Condition {
+    fn decode(input: ParseStream) -> syn::Result<Self> {
+        let (tag, span) = decode_tag(input)?;
+        Ok(match tag.as_str() {
+            "capture" => Condition::Capture(Decode::decode(input)?),
+            "call" => {
+                Condition::Call { receiver: Decode::decode(input)?, method: Decode::decode(input)? }
+            }
+            "eq" => Condition::Eq(Decode::decode(input)?, Decode::decode(input)?),
+            "not" => Condition::Not(Box::new(Decode::decode(input)?)),
+            "and" => {
+                Condition::And(Box::new(Decode::decode(input)?), Box::new(Decode::decode(input)?))
+            }
+            "or" => {
+                Condition::Or(Box::new(Decode::decode(input)?), Box::new(Decode::decode(input)?))
+            }
+            _ => synerr!(span, "unknown condition `{tag}`"),
+        })
+    }
+}
+
+impl Encode for Operand {
+    fn encode(&self, out: &mut Vec<TokenTree>) {
+        match self {
+            Operand::Capture(path) => {
+                out.push(tag("capture"));
+                path.encode(out);
+            }
+            Operand::Ident(ident) => {
+                out.push(tag("ident"));
+                ident.encode(out);
+            }
+            Operand::Literal(literal) => {
+                out.push(tag("literal"));
+                literal.encode(out);
+            }
+        }
+    }
+}
+
+impl Decode for Operand {
+    fn decode(input: ParseStream) -> syn::Result<Self> {
+        let (tag, span) = decode_tag(input)?;
+        Ok(match tag.as_str() {
+            "capture" => Operand::Capture(Decode::decode(input)?),
+            "ident" => Operand::Ident(Decode::decode(input)?),
+            "literal" => Operand::Literal(Decode::decode(input)?),
+            _ => synerr!(span, "unknown operand `{tag}`"),
+        })
+    }
+}
+
+impl Encode for ForExpr {
+    fn encode(&self, out: &mut Vec<TokenTree>) {
+        match self {
+            ForExpr::Path(path) => {
+                out.push(tag("path"));
+                path.encode(out);
+            }
+            ForExpr::Zip(paths) => {
+                out.push(tag("zip"));
+                paths.encode(out);
+            }
+        }
+    }
+}
+
+impl Decode for ForExpr {
+    fn decode(input: ParseStream) -> syn::Result<Self> {
+        let (tag, span) = decode_tag(input)?;
+        Ok(match tag.as_str() {
+            "path" => ForExpr::Path(Decode::decode(input)?),
+            "zip" => ForExpr::Zip(Decode::decode(input)?),
+            _ => synerr!(span, "unknown `for` expression `{tag}`"),
+        })
+    }
+}
+
+impl Encode for Expr {
+    fn encode(&self, out: &mut Vec<TokenTree>) {
+        match self {
+            Expr::Path(path) => {
+                out.push(tag("path"));
+                path.encode(out);
+            }
+            Expr::Literal(literal) => {
+                out.push(tag("literal"));
+                literal.encode(out);
+            }
+        }
+    }
+}
+
+impl Decode for Expr {
+    fn decode(input: ParseStream) -> syn::Result<Self> {
+        let (tag, span) = decode_tag(input)?;
+        Ok(match tag.as_str() {
+            "path" => Expr::Path(Decode::decode(input)?),
+            "literal" => Expr::Literal(Decode::decode(input)?),
+            _ => synerr!(span, "unknown expression `{tag}`"),
+        })
+    }
+}
+
+impl Encode for Path {
+    fn encode(&self, out: &mut Vec<TokenTree>) {
+        self.0.encode(out);
+    }
+}
+
+impl Decode for Path {
+    fn decode(input: ParseStream) -> syn::Result<Self> {
+        decode_bracketed(input, String::decode).map(Path)
+    }
+}
+
+impl Encode for GroupDelimiter {
+    fn encode(&self, out: &mut Vec<TokenTree>) {
+        out.push(tag(match self {
+            GroupDelimiter::Parenthesis => "parenthesis",
+            GroupDelimiter::Bracket => "bracket",
+            GroupDelimiter::Brace => "brace",
+            GroupDelimiter::None => "none",
+        }));
+    }
+}
+
+impl Decode for GroupDelimiter {
+    fn decode(input: ParseStream) -> syn::Result<Self> {
+        let (tag, span) = decode_tag(input)?;
+        Ok(match tag.as_str() {
+            "parenthesis" => GroupDelimiter::Parenthesis,
+            "bracket" => GroupDelimiter::Bracket,
+            "brace" => GroupDelimiter::Brace,
+            "none" => GroupDelimiter::None,
+            _ => synerr!(span, "unknown delimiter `{tag}`"),
+        })
+    }
+}
+
+impl Encode for Punct {
+    fn encode(&self, out: &mut Vec<TokenTree>) {
+        out.push(TokenTree::Literal(Literal::character(self.char)));
+        out.push(tag(match self.spacing {
+            Spacing::Alone => "alone",
+            Spacing::Joint => "joint",
+        }));
+    }
+}
+
+impl Decode for Punct {
+    fn decode(input: ParseStream) -> syn::Result<Self> {
+        let char = input.parse::<syn::LitChar>()?.value();
+        let (tag, span) = decode_tag(input)?;
+        let spacing = match tag.as_str() {
+            "alone" => Spacing::Alone,
+            "joint" => Spacing::Joint,
+            _ => synerr!(span, "unknown spacing `{tag}`"),
+        };
+        Ok(Punct { char, spacing })
+    }
+}
+
+impl Encode for MacroLiteral {
+    fn encode(&self, out: &mut Vec<TokenTree>) {
+        self.0.encode(out);
+    }
+}
+
+impl Decode for MacroLiteral {
+    fn decode(input: ParseStream) -> syn::Result<Self> {
+        Decode::decode(input).map(MacroLiteral)
+    }
+}
@@ -72,9 +72,3 @@ macro_rules! synerr {
         return Err(syn::Error::new($span, format!($message $(, $e)*)))
     };
 }
-
-macro_rules! synbail {
-    ($span:expr, $message:literal $(, $e:expr)*) => {
-        return syn::Error::new($span, format!($message $(, $e)*)).into_compile_error().into()
-    };
-}
@@ -0,0 +1,213 @@
+//! Built-in fragment matchers, i.e. the fragment specifiers known from `macro_rules!`.
+//!
+//! These are implemented by running a `syn` parser over a prefix of the input. The consumed
+//! token trees are wrapped in an invisible group, so a fragment is always captured as a single
+//! token tree, like `$t:ty` in `macro_rules!`.
+
+use proc_macro2::{Delimiter, Group, Literal, TokenStream, TokenTree};
+use syn::{
+    Token,
+    parse::{ParseBuffer, ParseStream, Parser},
+};
+
+/// The refined literal matchers, e.g. `$s:str` or `$n:int`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) enum LiteralKind {
+    Str,
+    ByteStr,
+    CStr,
+    Int,
+    Float,
+    Char,
+    Byte,
+    Bool,
+}
+
+impl LiteralKind {
+    pub(super) fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "str" => LiteralKind::Str,
+            "byte_str" => LiteralKind::ByteStr,
+            "c_str" => LiteralKind::CStr,
+            "int" => LiteralKind::Int,
+            "float" => LiteralKind::Float,
+            "char" => LiteralKind::Char,
+            "byte" => LiteralKind::Byte,
+            "bool" => LiteralKind::Bool,
+            _ => return None,
+        })
+    }
+
+    pub(super) fn description(self) -> &'static str {
+        match self {
+            LiteralKind::Str => "a string literal",
+            LiteralKind::ByteStr => "a byte string literal",
+            LiteralKind::CStr => "a C string literal",
+            LiteralKind::Int => "an integer literal",
+            LiteralKind::Float => "a float literal",
+            LiteralKind::Char => "a character literal",
+            LiteralKind::Byte => "a byte literal",
+            LiteralKind::Bool => "`true` or `false`",
+        }
+    }
+
+    fn matches(self, literal: &Literal) -> bool {
+        matches!(
+            (self, syn::Lit::new(literal.clone())),
+            (LiteralKind::Str, syn::Lit::Str(_))
+                | (LiteralKind::ByteStr, syn::Lit::ByteStr(_))
+                | (LiteralKind::CStr, syn::Lit::CStr(_))
+                | (LiteralKind::Int, syn::Lit::Int(_))
+                | (LiteralKind::Float, syn::Lit::Float(_))
+                | (LiteralKind::Char, syn::Lit::Char(_))
+                | (LiteralKind::Byte, syn::Lit::Byte(_))
+        )
+    }
+}
+
+/// Matches a literal of the given kind. Numbers may be negative, in which case the `-` and the
+/// literal are wrapped in an invisible group.
+pub(super) fn typed_literal(tts: &[TokenTree], kind: LiteralKind) -> Option<(TokenTree, usize)> {
+    match tts {
+        [TokenTree::Ident(ident), ..] if kind == LiteralKind::Bool => {
+            (ident == "true" || ident == "false").then(|| (ident.clone().into(), 1))
+        }
+        [minus @ TokenTree::Punct(punct), lit @ TokenTree::Literal(literal), ..]
+            if punct.as_char() == '-'
+                && matches!(kind, LiteralKind::Int | LiteralKind::Float)
+                && kind.matches(literal) =>
+        {
+            let stream = TokenStream::from_iter([minus.clone(), lit.clone()]);
+            Some((Group::new(Delimiter::None, stream).into(), 2))
+        }
+        [TokenTree::Literal(literal), ..] if kind.matches(literal) => {
+            Some((literal.clone().into(), 1))
+        }
+        _ => None,
+    }
+}
+
+/// Runs `parser` on the start of `tts`, where `positions` are parse streams positioned before
+/// every token tree of `tts` and after the last one. On success, returns the consumed token trees
+/// (wrapped in an invisible group) and their number.
+///
+/// Nothing is copied until the end of the fragment is known. Then the fragment is parsed again on
+/// its own, since only that detects unexpected tokens in nested groups, e.g. in `#[foo bar]`.
+pub(super) fn parse_prefix(
+    tts: &[TokenTree],
+    positions: &[ParseBuffer],
+    parser: fn(ParseStream) -> syn::Result<()>,
+) -> Option<(TokenTree, usize)> {
+    let fork = positions[0].fork();
+    parser(&fork).ok()?;
+    let end = fork.cursor();
+    let increment = positions.iter().position(|position| position.cursor() == end)?;
+
+    let stream = TokenStream::from_iter(tts[..increment].iter().cloned());
+    parser.parse2(stream.clone()).ok()?;
+    Some((Group::new(Delimiter::None, stream).into(), increment))
+}
+
+pub(super) fn ty(input: ParseStream) -> syn::Result<()> {
+    input.parse::<syn::Type>().map(drop)
+}
+
+pub(super) fn expr(input: ParseStream) -> syn::Result<()> {
+    input.parse::<syn::Expr>().map(drop)
+}
+
+/// An expression as in edition 2021, which can't start with `_` or a `const` block
+pub(super) fn expr_2021(input: ParseStream) -> syn::Result<()> {
+    if input.peek(Token![_]) || input.peek(Token![const]) {
+        return Err(input.error("expected an expression"));
+    }
+    expr(input)
+}
+
+/// A path in type position, which may start with `::` or be qualified (`<T as Trait>::Assoc`).
+/// Generic arguments are accepted both with and without turbofish, as are the parenthesized
+/// arguments of `Fn` traits (`Fn(u8) -> u8`) on any segment.
+pub(super) fn path(input: ParseStream) -> syn::Result<()> {
+    // `syn` only accepts parenthesized arguments in trait bounds, so each part of the path up to
+    // such arguments is parsed on its own; the part after them starts with `::`
+    loop {
+        let path = input.parse::<syn::TypePath>()?.path;
+        let last = path.segments.last().unwrap();
+        if !last.arguments.is_none() || !input.peek(syn::token::Paren) {
+            return Ok(());
+        }
+        input.parse::<syn::ParenthesizedGenericArguments>()?;
+        if !input.peek(Token![::]) {
+            return Ok(());
+        }
+    }
+}
+
+/// A pattern, which may contain top-level or-patterns (as in edition 2021).
+pub(super) fn pat(input: ParseStream) -> syn::Result<()> {
+    syn::Pat::parse_multi_with_leading_vert(input).map(drop)
+}
+
+/// A pattern without top-level or-patterns.
+pub(super) fn pat_param(input: ParseStream) -> syn::Result<()> {
+    syn::Pat::parse_single(input).map(drop)
+}
+
+/// A statement without a trailing semicolon, except for items that require one.
+pub(super) fn stmt(input: ParseStream) -> syn::Result<()> {
+    if input.fork().parse::<syn::Item>().is_ok() {
+        return input.parse::<syn::Item>().map(drop);
+    }
+
+    input.call(syn::Attribute::parse_outer)?;
+    if input.peek(Token![let]) {
+        input.parse::<Token![let]>()?;
+        syn::Pat::parse_single(input)?;
+        if input.peek(Token![:]) {
+            input.parse::<Token![:]>()?;
+            input.parse::<syn::Type>()?;
+        }
+        if input.peek(Token![=]) {
+            input.parse::<Token![=]>()?;
+            input.parse::<syn::Expr>()?;
+            if input.peek(Token![else]) {
+                input.parse::<Token![else]>()?;
+                input.parse::<syn::Block>()?;
+            }
+        }
+        Ok(())
+    } else {
+        input.parse::<syn::Expr>().map(drop)
+    }
+}
+
+pub(super) fn block(input: ParseStream) -> syn::Result<()> {
+    input.parse::<syn::Block>().map(drop)
+}
+
+pub(super) fn item(input: ParseStream) -> syn::Result<()> {
+    input.parse::<syn::Item>().map(drop)
+}
+
+/// The content of an attribute, e.g. `derive(Debug)` in `#[derive(Debug)]`.
+pub(super) fn meta(input: ParseStream) -> syn::Result<()> {
+    input.parse::<syn::Meta>().map(drop)
+}
+
+/// An outer (`#[...]`) or inner (`#![...]`) attribute. This includes doc comments, which are
+/// passed to macros as `#[doc = "..."]`.
+pub(super) fn attr(input: ParseStream) -> syn::Result<()> {
+    input.parse::<Token![#]>()?;
+    if input.peek(Token![!]) {
+        input.parse::<Token![!]>()?;
+    }
+    let content;
+    syn::bracketed!(content in input);
+    content.parse::<syn::Meta>().map(drop)
+}
+
+/// A visibility such as `pub` or `pub(crate)`. Like `$v:vis` in `macro_rules!`, this also matches
+/// the empty visibility, so it never fails.
+pub(super) fn vis(input: ParseStream) -> syn::Result<()> {
+    input.parse::<syn::Visibility>().map(drop)
+}
@@ -1,76 +1,231 @@
-use proc_macro2::{Literal, Span, TokenStream, TokenTree};
+use std::str::FromStr;
 
-use crate::{MResult, macros::replacement::Expr};
+use proc_macro2::{Ident, Literal, Span, TokenStream, TokenTree};
+
+use crate::{
+    MResult,
+    macros::{Path, replacement::Expr},
+};
 
 use super::Match;
 
+/// The first repetition of a capture
 pub(crate) fn first(
     args: &[Expr],
     matches: &Match,
     span: Span,
     result: &mut TokenStream,
 ) -> MResult<()> {
-    if args.len() != 1 {
-        bail!("expected 1 argument, got {}", args.len() => span);
-    }
-    if let Some(tt) = matches.find_child(&args[0].path) {
-        if tt.is_empty() {
-            bail!("the argument `{:?}` is empty", &args[0].path => span);
-        }
-        let first = &tt[0..1];
-        result.extend(first.iter().cloned());
-    } else {
-        bail!("the argument `{:?}` does not exist", &args[0].path => span);
-    }
+    let path = single_capture(args, span)?;
+    let Some(first) = matches.resolve(path).first().copied() else {
+        bail!("the argument `{path:?}` is empty" => span);
+    };
+    result.extend(first.tts.iter().cloned());
 
     Ok(())
 }
 
+/// The last repetition of a capture
 pub(crate) fn last(
     args: &[Expr],
     matches: &Match,
     span: Span,
     result: &mut TokenStream,
 ) -> MResult<()> {
-    if args.len() != 1 {
-        bail!("expected 1 argument, got {}", args.len() => span);
+    let path = single_capture(args, span)?;
+    let Some(last) = matches.resolve(path).last().copied() else {
+        bail!("the argument `{path:?}` is empty" => span);
+    };
+    result.extend(last.tts.iter().cloned());
+
+    Ok(())
+}
+
+/// The number of repetitions of a capture
+pub(crate) fn count(
+    args: &[Expr],
+    matches: &Match,
+    span: Span,
+    result: &mut TokenStream,
+) -> MResult<()> {
+    let path = single_capture(args, span)?;
+    if !matches.declares(path) {
+        bail!("the argument `{path:?}` does not exist" => span);
     }
-    if let Some(tt) = matches.find_child(&args[0].path) {
-        if tt.is_empty() {
-            bail!("the argument `{:?}` is empty", &args[0].path => span);
+    let count = matches.resolve(path).len();
+    result.extend([TokenTree::Literal(Literal::usize_unsuffixed(count))]);
+
+    Ok(())
+}
+
+/// Concatenates captures and literals. If all pieces are identifiers or literals consisting of
+/// identifier characters, the result is an identifier, otherwise it is a string literal.
+pub(crate) fn concat(
+    args: &[Expr],
+    matches: &Match,
+    span: Span,
+    result: &mut TokenStream,
+) -> MResult<()> {
+    if args.is_empty() {
+        bail!("expected at least 1 argument" => span);
+    }
+
+    let mut text = String::new();
+    let mut ident_like = true;
+    for arg in args {
+        match arg {
+            Expr::Path(path) => {
+                let Some(tt) = matches.find_child(path) else {
+                    bail!("the argument `{path:?}` does not exist" => span);
+                };
+                if tt.len() != 1 {
+                    ident_like = false;
+                }
+                for tt in &tt {
+                    let piece = match tt {
+                        TokenTree::Ident(ident) => {
+                            let ident = ident.to_string();
+                            ident.strip_prefix("r#").map(str::to_string).unwrap_or(ident)
+                        }
+                        TokenTree::Literal(literal) => literal_value(literal),
+                        tt => tt.to_string(),
+                    };
+                    ident_like &= matches!(tt, TokenTree::Ident(_)) || is_ident_chars(&piece);
+                    text.push_str(&piece);
+                }
+            }
+            Expr::Literal(literal) => {
+                let Ok(literal) = Literal::from_str(&literal.0) else {
+                    bail!("invalid literal `{}`", literal.0 => span);
+                };
+                let piece = literal_value(&literal);
+                ident_like &= is_ident_chars(&piece);
+                text.push_str(&piece);
+            }
         }
-        let last = &tt[tt.len() - 1..];
-        result.extend(last.iter().cloned());
+    }
+
+    if ident_like {
+        result.extend([TokenTree::Ident(make_ident(&text, span)?)]);
     } else {
-        bail!("the argument `{:?}` does not exist", &args[0].path => span);
+        result.extend([TokenTree::Literal(Literal::string(&text))]);
     }
 
     Ok(())
 }
 
-pub(crate) fn count(
+/// Converts the case of an identifier. `kebab_case` produces a string literal, since the result
+/// isn't a valid identifier.
+pub(crate) fn convert_case(
+    case: &str,
     args: &[Expr],
     matches: &Match,
     span: Span,
     result: &mut TokenStream,
 ) -> MResult<()> {
-    if args.len() != 1 {
-        bail!("expected 1 argument, got {}", args.len() => span);
+    let path = single_capture(args, span)?;
+    let ident = match matches.find_child(path).as_deref() {
+        Some([TokenTree::Ident(ident)]) => ident.to_string(),
+        Some([]) | None => bail!("the argument `{path:?}` does not exist" => span),
+        Some(tts) => {
+            let tokens = TokenStream::from_iter(tts.iter().cloned());
+            bail!("expected an identifier, got `{tokens}`" => span);
+        }
+    };
+    let words = split_words(ident.strip_prefix("r#").unwrap_or(&ident));
+    if words.is_empty() {
+        bail!("`{ident}` doesn't contain any words" => span);
     }
-    if let Some(tt) = matches.find_child(&args[0].path) {
-        result.extend([TokenTree::Literal(Literal::usize_unsuffixed(tt.len()))]);
+
+    let capitalize = |word: &str| {
+        let mut chars = word.chars();
+        let first = chars.next().into_iter().flat_map(char::to_uppercase);
+        first.chain(chars.flat_map(char::to_lowercase)).collect::<String>()
+    };
+    let lower = words.iter().map(|word| word.to_lowercase());
+    let text = match case {
+        "snake_case" => lower.collect::<Vec<_>>().join("_"),
+        "kebab_case" => lower.collect::<Vec<_>>().join("-"),
+        "shouty_snake_case" => words.iter().map(|w| w.to_uppercase()).collect::<Vec<_>>().join("_"),
+        "pascal_case" => words.iter().map(|w| capitalize(w)).collect(),
+        _ => lower.take(1).chain(words[1..].iter().map(|w| capitalize(w))).collect(),
+    };
+
+    if case == "kebab_case" {
+        result.extend([TokenTree::Literal(Literal::string(&text))]);
     } else {
-        bail!("the argument `{:?}` does not exist", &args[0].path => span);
+        result.extend([TokenTree::Ident(make_ident(&text, span)?)]);
     }
 
     Ok(())
 }
 
-pub(crate) fn concat(
-    _args: &[Expr],
-    _matches: &Match,
-    span: Span,
-    _result: &mut TokenStream,
-) -> MResult<()> {
-    bail!("unimplemented" => span);
+/// Splits an identifier at underscores and case changes: `HTTPServer_v2` is split into `HTTP`,
+/// `Server` and `v2`
+fn split_words(ident: &str) -> Vec<&str> {
+    let mut words = Vec::new();
+    for part in ident.split('_').filter(|part| !part.is_empty()) {
+        let chars = part.char_indices().collect::<Vec<_>>();
+        let mut start = 0;
+        for (i, window) in chars.windows(2).enumerate() {
+            let ((_, prev), (idx, c)) = (window[0], window[1]);
+            let next_is_lower = chars.get(i + 2).is_some_and(|&(_, next)| next.is_lowercase());
+            let boundary = c.is_uppercase() && (!prev.is_uppercase() || next_is_lower);
+            if boundary {
+                words.push(&part[start..idx]);
+                start = idx;
+            }
+        }
+        words.push(&part[start..]);
+    }
+    words
+}
+
+/// Creates an identifier, which is raw if `text` is a keyword
+fn make_ident(text: &str, span: Span) -> MResult<Ident> {
+    let mut chars = text.chars();
+    let valid = chars.next().is_some_and(|c| c == '_' || unicode_ident::is_xid_start(c))
+        && chars.all(unicode_ident::is_xid_continue)
+        && text != "_";
+    if !valid {
+        bail!("`{text}` is not a valid identifier" => span);
+    }
+
+    if RAW_KEYWORDS.contains(&text) {
+        Ok(Ident::new_raw(text, span))
+    } else {
+        Ok(Ident::new(text, span))
+    }
+}
+
+/// Keywords that can be used as raw identifiers
+const RAW_KEYWORDS: &[&str] = &[
+    "as", "async", "await", "break", "const", "continue", "dyn", "else", "enum", "extern", "false",
+    "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub", "ref",
+    "return", "static", "struct", "trait", "true", "type", "unsafe", "use", "where", "while",
+    "abstract", "become", "box", "do", "final", "gen", "macro", "override", "priv", "try",
+    "typeof", "unsized", "virtual", "yield",
+];
+
+fn single_capture(args: &[Expr], span: Span) -> MResult<&Path> {
+    match args {
+        [Expr::Path(path)] => Ok(path),
+        [Expr::Literal(literal)] => bail!("expected a capture, got `{}`", literal.0 => span),
+        _ => bail!("expected 1 argument, got {}", args.len() => span),
+    }
+}
+
+/// The content of string, character and integer literals, or the literal as written otherwise
+fn literal_value(literal: &Literal) -> String {
+    let tokens = TokenStream::from(TokenTree::Literal(literal.clone()));
+    match syn::parse2::<syn::Lit>(tokens) {
+        Ok(syn::Lit::Str(lit)) => lit.value(),
+        Ok(syn::Lit::Char(lit)) => lit.value().to_string(),
+        Ok(syn::Lit::Int(lit)) => lit.base10_digits().to_string(),
+        _ => literal.to_string(),
+    }
+}
+
+fn is_ident_chars(text: &str) -> bool {
+    !text.is_empty() && text.chars().all(unicode_ident::is_xid_continue)
 }
@@ -1,67 +1,154 @@
-use std::{collections::HashMap, fmt};
+use std::{
+    cmp::Ordering,
+    collections::{HashMap, HashSet},
+    fmt,
+    ops::Range,
+    rc::Rc,
+};
 
 use proc_macro2::{Delimiter, Group, Spacing, Span, TokenStream, TokenTree};
+use syn::parse::{ParseBuffer, ParseStream, Parser, discouraged::Speculative};
 
 use crate::{
     Rules,
-    errors::MResult,
+    errors::{MResult, MacroError},
+    expand::fragments::{self, LiteralKind},
     macros::{
-        Literal, Path,
-        pattern::{Pattern, PatternMatcher, Patterns, Quantifier},
+        Alternative, Literal, Path,
+        pattern::{Pattern, PatternMatcher, Patterns, Quantifier, RepeatKind},
     },
 };
 
 #[derive(Default, Clone)]
 pub(super) struct Match {
     pub(super) tts: Vec<TokenTree>,
-    pub(super) children: HashMap<String, Match>,
+    /// The matches of the repetitions of every capture, in input order
+    pub(super) children: HashMap<String, Vec<Match>>,
+    /// The type of the matcher that produced this match, e.g. `ident` or the name of a `match` rule
+    pub(super) ty: Option<String>,
+    /// The label of the alternative of the `match` rule that matched
+    pub(super) label: Option<String>,
+    /// For the index binding of a `$for`: the index and the number of iterations
+    pub(super) iteration: Option<(usize, usize)>,
+    /// The names of the captures declared by the patterns, including optional captures that
+    /// didn't match
+    pub(super) declared: HashSet<String>,
 }
 
 impl Match {
     fn new(tts: Vec<TokenTree>) -> Self {
-        Self { tts, children: HashMap::default() }
+        Self {
+            tts,
+            children: HashMap::default(),
+            ty: None,
+            label: None,
+            iteration: None,
+            declared: HashSet::new(),
+        }
     }
 
-    fn add_child(&mut self, key: String, tt: TokenTree) {
-        let existing = self.children.entry(key).or_default();
-        existing.tts.push(tt);
+    fn add_child(&mut self, key: String, ty: &str, tt: TokenTree) {
+        let mut value = Match::new(vec![tt]);
+        value.ty = Some(ty.to_string());
+        self.children.entry(key).or_default().push(value);
     }
 
     fn nest(&mut self, key: &str, mat: Match) {
-        if let Some(value) = self.children.get_mut(key) {
-            value.tts.extend(mat.tts);
-            for (inner_key, mat) in mat.children {
-                value.nest(&inner_key, mat);
-            }
-        } else {
-            _ = self.children.insert(key.to_string(), mat);
+        self.children.entry(key.to_string()).or_default().push(mat);
+    }
+
+    /// Binds `key` to a single match, replacing the existing capture
+    pub(super) fn insert(&mut self, key: String, value: Match) {
+        _ = self.children.insert(key, vec![value]);
+    }
+
+    pub(super) fn insert_index(&mut self, key: &str, index: usize, len: usize) {
+        let tt = TokenTree::Literal(proc_macro2::Literal::usize_unsuffixed(index));
+        let mut value = Match::new(vec![tt]);
+        value.iteration = Some((index, len));
+        self.insert(key.to_string(), value);
+    }
+
+    /// The repetitions of the capture at `path`. Every segment of the path descends into all
+    /// repetitions of the previous segment, so the result is flattened.
+    pub(super) fn resolve(&self, path: &Path) -> Vec<&Match> {
+        let mut current = vec![self];
+        for segment in &path.0 {
+            current = current
+                .into_iter()
+                .flat_map(|mat| mat.children.get(segment).into_iter().flatten())
+                .collect();
         }
+        current
+    }
+
+    /// The repetitions of the capture at `path`, for iterating with `$for`
+    pub(super) fn items(&self, path: &Path) -> Vec<Match> {
+        self.resolve(path).into_iter().cloned().collect()
     }
 
-    pub(super) fn replace_child(&mut self, key: &str, tt: TokenTree) {
-        if let Some(value) = self.children.get_mut(key) {
-            value.tts.clear();
-            value.tts.push(tt);
-        } else {
-            _ = self.children.insert(key.to_string(), Match::new(vec![tt]));
+    /// The capture at `path`. If it was repeated, the repetitions are merged into one match.
+    pub(super) fn get(&self, path: &Path) -> Option<Match> {
+        match self.resolve(path)[..] {
+            [] => None,
+            [single] => Some(single.clone()),
+            ref repetitions => Some(merge(repetitions)),
         }
     }
 
-    pub(super) fn find_child(&self, path: &Path) -> Option<Vec<TokenTree>> {
-        // TODO: Avoid expensive clones
-        let mut results = vec![(self.tts.clone(), &self.children)];
+    /// Whether the capture at `path` exists, or the last segment of `path` is the label of the
+    /// alternative that produced the capture before it
+    pub(super) fn test(&self, path: &Path) -> bool {
+        if !self.resolve(path).is_empty() {
+            return true;
+        }
+        let Some((last, prefix)) = path.0.split_last() else {
+            return false;
+        };
+        let prefix = Path(prefix.to_vec());
+        self.resolve(&prefix).first().is_some_and(|parent| parent.label.as_ref() == Some(last))
+    }
 
+    /// Whether `path` names a capture, even one that didn't match. Segments after a capture that
+    /// didn't match can't be checked, so they are assumed to exist.
+    pub(super) fn declares(&self, path: &Path) -> bool {
+        let mut current = vec![self];
         for segment in &path.0 {
-            results = results
+            let declared =
+                |mat: &&Match| mat.declared.contains(segment) || mat.children.contains_key(segment);
+            if !current.is_empty() && !current.iter().any(declared) {
+                return false;
+            }
+            current = current
                 .into_iter()
-                .flat_map(|(_, children)| {
-                    children.get(segment.as_str()).map(|mat| (mat.tts.clone(), &mat.children))
-                })
+                .flat_map(|mat| mat.children.get(segment).into_iter().flatten())
                 .collect();
         }
+        true
+    }
+
+    /// The tokens of all repetitions of the capture at `path`
+    pub(super) fn find_child(&self, path: &Path) -> Option<Vec<TokenTree>> {
+        let repetitions = self.resolve(path);
+        Some(repetitions.into_iter().flat_map(|mat| mat.tts.iter().cloned()).collect())
+    }
+}
 
-        Some(results.into_iter().flat_map(|(result, _)| result).collect())
+/// Merges repetitions by concatenating their tokens and the repetitions of their captures
+fn merge(repetitions: &[&Match]) -> Match {
+    let mut merged = Match::new(Vec::new());
+    for mat in repetitions {
+        merged.tts.extend(mat.tts.iter().cloned());
+        for (key, children) in &mat.children {
+            merged.children.entry(key.clone()).or_default().extend(children.iter().cloned());
+        }
+        merged.declared.extend(mat.declared.iter().cloned());
+    }
+    if let Some(first) = repetitions.first() {
+        merged.ty = first.ty.clone();
+        merged.label = first.label.clone();
     }
+    merged
 }
 
 impl fmt::Debug for Match {
@@ -83,43 +170,137 @@ impl fmt::Debug for Match {
         f.debug_struct("Match")
             .field("tts", &DisplayTokens(&self.tts))
             .field("children", &self.children)
+            .field("ty", &self.ty)
+            .field("label", &self.label)
+            .field("iteration", &self.iteration)
             .finish()
     }
 }
 
-pub(crate) enum Matched {
-    Success { offset: usize },
-    Failed { error: PatternError },
+/// A partial match: the input was matched up to `offset`, producing `captures`.
+#[derive(Clone)]
+struct State {
+    offset: usize,
+    captures: Captures,
 }
 
-pub(crate) struct PatternError {
-    pattern: String,
-    last_offset: usize,
-    span: Span,
-    reasons: Option<Vec<PatternError>>,
+/// The captures of a partial match as a persistent list, newest first. States that are reached
+/// from the same state share its captures, so creating a state doesn't copy them. The captures
+/// are only turned into a [`Match`] once the input has been matched.
+#[derive(Clone, Default)]
+struct Captures(Option<Rc<CaptureNode>>);
+
+struct CaptureNode {
+    name: String,
+    capture: Capture,
+    prev: Captures,
 }
 
-impl PatternError {
-    fn new(pattern: String, span: Span, last_offset: usize) -> Self {
-        Self { pattern, span, last_offset, reasons: None }
-    }
+enum Capture {
+    /// A built-in matcher, which captures a single token tree
+    Token { ty: String, tt: TokenTree },
+    /// An alternative of a `match` rule, which consumed `tts[range]`
+    Rule {
+        ty: String,
+        alternative: usize,
+        tts: Rc<[TokenTree]>,
+        range: Range<usize>,
+        captures: Captures,
+    },
 }
 
-impl fmt::Display for PatternError {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        fn format(error: &PatternError, f: &mut fmt::Formatter<'_>, indent: usize) -> fmt::Result {
-            write!(f, "unmatched pattern `{}`", error.pattern)?;
-            if let Some(reasons) = &error.reasons {
-                write!(f, ", reasons:")?;
-                for reason in reasons {
-                    write!(f, "\n    {:indent$}- ", "")?;
-                    format(reason, f, indent + 4)?;
+impl Captures {
+    fn push(&self, name: &str, capture: Capture) -> Captures {
+        let node = CaptureNode { name: name.to_string(), capture, prev: self.clone() };
+        Captures(Some(Rc::new(node)))
+    }
+
+    fn build(&self, rules: &Rules) -> Match {
+        let mut nodes = Vec::new();
+        let mut current = &self.0;
+        while let Some(node) = current {
+            nodes.push(node);
+            current = &node.prev.0;
+        }
+
+        let mut result = Match::default();
+        for node in nodes.into_iter().rev() {
+            match &node.capture {
+                Capture::Token { ty, tt } => result.add_child(node.name.clone(), ty, tt.clone()),
+                Capture::Rule { ty, alternative, tts, range, captures } => {
+                    let alternative = &rules.matches[ty][*alternative];
+                    let mut child = captures.build(rules);
+                    child.tts = tts[range.clone()].to_vec();
+                    groupify(&mut child.tts);
+                    // matchers imported from other `rules!` blocks are named `rule::dependency`
+                    child.ty = Some(ty.rsplit("::").next().unwrap().to_string());
+                    child.label = alternative.label.clone();
+                    collect_capture_names(&alternative.patterns.0, &mut child.declared);
+                    result.nest(&node.name, child);
                 }
             }
-            Ok(())
         }
+        result
+    }
+}
+
+impl Drop for Captures {
+    // dropping a long list recursively could overflow the stack
+    fn drop(&mut self) {
+        let mut next = self.0.take();
+        while let Some(node) = next {
+            next = Rc::try_unwrap(node).ok().and_then(|mut node| node.prev.0.take());
+        }
+    }
+}
+
+/// The token trees of a group, and forks of a parse stream of them that are positioned before
+/// every token tree and after the last one, so fragments can be parsed without copying the rest
+/// of the input
+struct Input<'a> {
+    tts: Rc<[TokenTree]>,
+    positions: Vec<ParseBuffer<'a>>,
+}
+
+impl Input<'_> {
+    fn with<R>(stream: TokenStream, f: impl FnOnce(&Input) -> R) -> R {
+        let tts = stream.clone().into_iter().collect::<Rc<[TokenTree]>>();
+        let parser = |parse_stream: ParseStream| {
+            let mut positions = vec![parse_stream.fork()];
+            for _ in 0..tts.len() {
+                let position = positions.last().unwrap().fork();
+                position.step(|cursor| match cursor.token_tree() {
+                    Some((_, next)) => Ok(((), next)),
+                    None => Err(cursor.error("unexpected end of input")),
+                })?;
+                positions.push(position);
+            }
+            parse_stream.advance_to(positions.last().unwrap());
+            Ok(f(&Input { tts, positions }))
+        };
+        parser.parse2(stream).expect("the parse stream has as many token trees as the stream")
+    }
+}
 
-        format(self, f, 0)
+pub(crate) struct PatternError {
+    /// The pattern that didn't match, or `None` if the input should have ended
+    pattern: Option<String>,
+    /// The offset of the token, preceded by the offsets of the groups containing it
+    position: Vec<usize>,
+    span: Span,
+    expected: Option<&'static str>,
+}
+
+impl fmt::Display for PatternError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.pattern {
+            Some(pattern) => write!(f, "unmatched pattern `{pattern}`")?,
+            None => write!(f, "unexpected token")?,
+        }
+        if let Some(expected) = self.expected {
+            write!(f, ", expected {expected}")?;
+        }
+        Ok(())
     }
 }
 
@@ -128,236 +309,375 @@ pub(crate) fn match_patterns(
     patterns: &[Pattern],
     stream: TokenStream,
 ) -> MResult<Match> {
-    let tts = stream.clone().into_iter().collect::<Vec<_>>();
-    let mut result = Match::new(tts);
+    let mut matcher = Matcher { rules, steps: 0, group_offsets: Vec::new(), errors: Vec::new() };
+
+    Input::with(stream, |input| {
+        let start = State { offset: 0, captures: Captures::default() };
+        let states = matcher.match_seq(input, patterns, vec![start])?;
+        let Some(state) = matcher.complete(&input.tts, states) else {
+            return Err(matcher.into_error());
+        };
+
+        let mut result = state.captures.build(rules);
+        result.tts = input.tts.to_vec();
+        groupify(&mut result.tts);
+        collect_capture_names(patterns, &mut result.declared);
+
+        Ok(result)
+    })
+}
 
-    match match_patterns_impl(rules, patterns, 0, &mut result)? {
-        Matched::Success { offset } if offset < result.tts.len() => {
-            bail!("unexpected token" => result.tts[offset].span());
-        }
-        Matched::Success { .. } => {}
-        Matched::Failed { error, .. } => {
-            return Err(crate::MacroError {
-                message: error.to_string(),
-                span: error.span,
-                stream: None,
-            });
+fn collect_capture_names(patterns: &[Pattern], names: &mut HashSet<String>) {
+    for pattern in patterns {
+        match pattern {
+            Pattern::Group(group) => collect_capture_names(&group.content, names),
+            Pattern::Matcher(matcher) => _ = names.insert(matcher.get_name().to_string()),
+            _ => {}
         }
     }
-    groupify(&mut result.tts);
-
-    Ok(result)
 }
 
-pub(crate) fn match_patterns_impl(
-    rules: &Rules,
-    patterns: &[Pattern],
-    offset: usize,
-    result: &mut Match,
-) -> MResult<Matched> {
-    let mut pattern_idx = 0;
-    let mut offset = offset;
-
-    while pattern_idx < patterns.len() {
-        let pat = &patterns[pattern_idx];
-
-        if offset >= result.tts.len() {
-            if let Pattern::Matcher(PatternMatcher { repeat: Some(repeat), .. }) = pat {
-                if let Quantifier::QuestionMark | Quantifier::Star = repeat.quantifier {
-                    pattern_idx += 1;
-                    continue;
-                }
-            }
+/// Matches patterns against token trees, with backtracking.
+///
+/// Instead of committing to the first alternative or repetition count that matches, all states
+/// reachable by a pattern are produced in order of preference. Since whether the rest of the
+/// input matches only depends on the offset, not on the captures, only the most preferred state
+/// is kept for every offset. This has the same result as depth-first backtracking, but the
+/// number of states is bounded by the input length.
+struct Matcher<'a> {
+    rules: &'a Rules,
+    steps: usize,
+    /// Offsets of the groups that are currently being matched
+    group_offsets: Vec<usize>,
+    /// Errors at the furthest position that was reached
+    errors: Vec<PatternError>,
+}
 
-            let span = if offset == 0 { Span::call_site() } else { result.tts[offset - 1].span() };
-            return Ok(Matched::Failed { error: PatternError::new(pat.to_string(), span, offset) });
-        }
-        match match_pattern(rules, pat, offset, result)? {
-            Matched::Success { offset: new_offset } => {
-                offset = new_offset;
+impl Matcher<'_> {
+    fn match_seq(
+        &mut self,
+        input: &Input,
+        patterns: &[Pattern],
+        mut states: Vec<State>,
+    ) -> MResult<Vec<State>> {
+        for pat in patterns {
+            let mut next = Vec::new();
+            for state in states {
+                next.extend(self.match_pattern(input, pat, state)?);
             }
-            failed @ Matched::Failed { .. } => {
-                return Ok(failed);
+            states = dedup(next);
+            if states.is_empty() {
+                break;
             }
         }
-        pattern_idx += 1;
+        Ok(states)
     }
 
-    Ok(Matched::Success { offset })
-}
+    fn match_pattern(&mut self, input: &Input, pat: &Pattern, state: State) -> MResult<Vec<State>> {
+        let tts = &input.tts;
+        self.step(tts, state.offset, 1)?;
+        let offset = state.offset;
 
-pub(crate) fn match_pattern(
-    rules: &Rules,
-    pat: &Pattern,
-    mut offset: usize,
-    result: &mut Match,
-) -> MResult<Matched> {
-    match (pat, &result.tts[offset]) {
-        (Pattern::Group(ast_group), TokenTree::Group(group))
-            if ast_group.delimiter == group.delimiter() =>
-        {
-            let inner_result = match_patterns(rules, &ast_group.content, group.stream())?;
-            result.children.extend(inner_result.children);
-            Ok(Matched::Success { offset: offset + 1 })
-        }
-        (Pattern::Ident(ident), TokenTree::Ident(tt_ident)) if ident == &tt_ident.to_string() => {
-            Ok(Matched::Success { offset: offset + 1 })
-        }
-        (Pattern::Punct(punct), TokenTree::Punct(tt_punct)) if punct.char == tt_punct.as_char() => {
-            Ok(Matched::Success { offset: offset + 1 })
-        }
-        (Pattern::Literal(Literal(lit)), TokenTree::Literal(tt_lit))
-            if lit == &tt_lit.to_string() =>
-        {
-            Ok(Matched::Success { offset: offset + 1 })
+        match (pat, tts.get(offset)) {
+            (Pattern::Group(ast_group), Some(TokenTree::Group(group)))
+                if ast_group.delimiter == group.delimiter() =>
+            {
+                let start = State { offset: 0, captures: state.captures };
+
+                self.group_offsets.push(offset);
+                let complete = Input::with(group.stream(), |inner| {
+                    let states = self.match_seq(inner, &ast_group.content, vec![start])?;
+                    Ok(self.complete(&inner.tts, states))
+                })?;
+                self.group_offsets.pop();
+
+                Ok(complete
+                    .map(|state| State { offset: offset + 1, captures: state.captures })
+                    .into_iter()
+                    .collect())
+            }
+            (Pattern::Ident(ident), Some(TokenTree::Ident(tt_ident)))
+                if ident == &tt_ident.to_string() =>
+            {
+                Ok(vec![State { offset: offset + 1, ..state }])
+            }
+            (Pattern::Punct(punct), Some(TokenTree::Punct(tt_punct)))
+                if punct.char == tt_punct.as_char() =>
+            {
+                Ok(vec![State { offset: offset + 1, ..state }])
+            }
+            (Pattern::Literal(Literal(lit)), Some(TokenTree::Literal(tt_lit)))
+                if lit == &tt_lit.to_string() =>
+            {
+                Ok(vec![State { offset: offset + 1, ..state }])
+            }
+
+            (Pattern::Matcher(special), _) => self.match_repeat(input, special, state),
+
+            _ => {
+                self.fail(tts, offset, Some(pat.to_string()), None);
+                Ok(Vec::new())
+            }
         }
+    }
 
-        (Pattern::Matcher(special), tt) => {
-            let offset_no_leading_punct = offset;
-            if let Some(leading) = &special.leading_punct() {
-                match tt {
-                    TokenTree::Punct(punct) if punct.as_char() == leading.char => {
-                        offset += 1;
+    /// Matches a matcher with its quantifier. Repetitions are greedy: states with more
+    /// repetitions are preferred.
+    fn match_repeat(
+        &mut self,
+        input: &Input,
+        pat: &PatternMatcher,
+        state: State,
+    ) -> MResult<Vec<State>> {
+        let Some(repeat) = &pat.repeat else {
+            return self.match_matcher(input, pat, state);
+        };
+        let tts = &input.tts;
+        let (min, max) = match repeat.quantifier {
+            Quantifier::Star => (0, usize::MAX),
+            Quantifier::Plus => (1, usize::MAX),
+            Quantifier::QuestionMark => (0, 1),
+        };
+        let kind = repeat.interspersed.map(|i| i.kind);
+        let punct = repeat.interspersed.map(|i| i.punct.char);
+        let is_punct = |offset: usize| match (tts.get(offset), punct) {
+            (Some(TokenTree::Punct(p)), Some(c)) => p.as_char() == c,
+            _ => false,
+        };
+
+        // the states after 0, 1, 2, ... repetitions
+        let mut by_count = vec![vec![state]];
+        while by_count.len() <= max {
+            let count = by_count.len() - 1;
+            let mut next = Vec::new();
+
+            for state in &by_count[count] {
+                let mut starts = Vec::new();
+                if count == 0 {
+                    if kind == Some(RepeatKind::Leading) && is_punct(state.offset) {
+                        starts.push(State { offset: state.offset + 1, ..state.clone() });
+                    }
+                    starts.push(state.clone());
+                } else if let Some(c) = punct {
+                    if is_punct(state.offset) {
+                        starts.push(State { offset: state.offset + 1, ..state.clone() });
+                    } else {
+                        self.fail(tts, state.offset, Some(c.to_string()), None);
                     }
-                    _ => {}
+                } else {
+                    starts.push(state.clone());
                 }
-            }
 
-            match special_match(rules, special, offset, result)? {
-                Matched::Success { offset: offset_new } => offset = offset_new,
-                failed @ Matched::Failed { .. } => {
-                    if let Some(Quantifier::Star | Quantifier::QuestionMark) = special.quantifier()
-                    {
-                        // repeating 0 times is allowed
-                        return Ok(Matched::Success { offset: offset_no_leading_punct });
-                    } else {
-                        return Ok(failed);
+                for start in starts {
+                    for end in self.match_matcher(input, pat, start)? {
+                        // a repetition that matches nothing would repeat forever
+                        if count == 0 || end.offset > state.offset {
+                            next.push(end);
+                        }
                     }
                 }
             }
 
-            if let Some(repeat) = &special.repeat {
-                loop {
-                    let offset_no_trailing_punct = offset;
-                    if let Some(interspersed) = &repeat.interspersed {
-                        if let Some(TokenTree::Punct(punct)) = result.tts.get(offset) {
-                            if punct.as_char() == interspersed.punct.char {
-                                offset += 1;
-                            }
+            let next = dedup(next);
+            if next.is_empty() {
+                break;
+            }
+            by_count.push(next);
+        }
+
+        let mut results = Vec::new();
+        for (count, states) in by_count.into_iter().enumerate().rev().filter(|&(c, _)| c >= min) {
+            for state in states {
+                if count > 0 && kind == Some(RepeatKind::Trailing) && is_punct(state.offset) {
+                    results.push(State { offset: state.offset + 1, ..state.clone() });
+                }
+                results.push(state);
+            }
+        }
+        Ok(dedup(results))
+    }
+
+    /// Matches a single repetition of a matcher
+    fn match_matcher(
+        &mut self,
+        input: &Input,
+        pat: &PatternMatcher,
+        state: State,
+    ) -> MResult<Vec<State>> {
+        let tts = &input.tts;
+        self.step(tts, state.offset, 1)?;
+
+        let ty = pat.ty.as_str();
+        let name = pat.get_name();
+        let offset = state.offset;
+
+        if let Some(pattern_set) = self.rules.matches.get(ty) {
+            let mut results: Vec<State> = Vec::new();
+            let mut alternatives = Vec::<usize>::new();
+
+            for (alternative, Alternative { patterns: Patterns(patterns), .. }) in
+                pattern_set.iter().enumerate()
+            {
+                // every alternative starts without captures
+                let start = State { offset, captures: Captures::default() };
+
+                for end in self.match_seq(input, patterns, vec![start])? {
+                    if self.rules.options.deny_ambiguity {
+                        let other = results
+                            .iter()
+                            .zip(&alternatives)
+                            .find(|&(s, &a)| s.offset == end.offset && a != alternative);
+                        if let Some((_, other)) = other {
+                            let input =
+                                TokenStream::from_iter(tts[offset..end.offset].iter().cloned());
+                            bail!(
+                                "ambiguous input: {} and {} of `{ty}` both match `{input}`",
+                                pattern_set[*other].describe(*other),
+                                pattern_set[alternative].describe(alternative) => span_at(tts, offset)
+                            );
                         }
                     }
 
-                    match special_match(rules, special, offset, result)? {
-                        Matched::Success { offset: offset_new } => offset = offset_new,
-                        Matched::Failed { .. } => {
-                            if special.trailing_punct().is_some() {
-                                return Ok(Matched::Success { offset });
-                            } else {
-                                return Ok(Matched::Success { offset: offset_no_trailing_punct });
-                            }
-                        }
+                    let child = Capture::Rule {
+                        ty: ty.to_string(),
+                        alternative,
+                        tts: tts.clone(),
+                        range: offset..end.offset,
+                        captures: end.captures,
                     };
+                    let captures = state.captures.push(name, child);
+                    results.push(State { offset: end.offset, captures });
+                    alternatives.push(alternative);
                 }
             }
 
-            Ok(Matched::Success { offset })
+            return Ok(dedup(results));
         }
 
-        _ => Ok(Matched::Failed {
-            error: PatternError::new(pat.to_string(), result.tts[offset].span(), offset),
-        }),
+        let rest = &tts[offset..];
+        let positions = &input.positions[offset..];
+        let matched = match ty {
+            "tt" => rest.first().map(|tt| (tt.clone(), 1)),
+            "literal" => match rest.first() {
+                Some(TokenTree::Literal(lit)) => Some((lit.clone().into(), 1)),
+                _ => None,
+            },
+            "ident" => match rest.first() {
+                Some(TokenTree::Ident(ident)) => Some((ident.clone().into(), 1)),
+                _ => None,
+            },
+            "lifetime" => match rest {
+                [tt1 @ TokenTree::Punct(p), tt2 @ TokenTree::Ident(_), ..]
+                    if p.as_char() == '\'' && p.spacing() == Spacing::Joint =>
+                {
+                    let stream = TokenStream::from_iter([tt1.clone(), tt2.clone()]);
+                    Some((Group::new(Delimiter::None, stream).into(), 2))
+                }
+                _ => None,
+            },
+            "ty" => fragments::parse_prefix(rest, positions, fragments::ty),
+            "expr" => fragments::parse_prefix(rest, positions, fragments::expr),
+            "expr_2021" => fragments::parse_prefix(rest, positions, fragments::expr_2021),
+            "path" => fragments::parse_prefix(rest, positions, fragments::path),
+            "pat" => fragments::parse_prefix(rest, positions, fragments::pat),
+            "pat_param" => fragments::parse_prefix(rest, positions, fragments::pat_param),
+            "stmt" => fragments::parse_prefix(rest, positions, fragments::stmt),
+            "block" => fragments::parse_prefix(rest, positions, fragments::block),
+            "item" => fragments::parse_prefix(rest, positions, fragments::item),
+            "meta" => fragments::parse_prefix(rest, positions, fragments::meta),
+            "attr" => fragments::parse_prefix(rest, positions, fragments::attr),
+            "vis" => fragments::parse_prefix(rest, positions, fragments::vis),
+            _ => match LiteralKind::from_name(ty) {
+                Some(kind) => fragments::typed_literal(rest, kind),
+                None => bail!("unknown matcher `{ty}`" => span_at(tts, offset)),
+            },
+        };
+        let Some((tt, increment)) = matched else {
+            let expected = LiteralKind::from_name(ty).map(LiteralKind::description);
+            self.fail(tts, offset, Some(pat.to_string()), expected);
+            return Ok(Vec::new());
+        };
+
+        // parsing a fragment takes time proportional to its length
+        self.step(tts, offset, increment)?;
+        let captures = state.captures.push(name, Capture::Token { ty: ty.to_string(), tt });
+        Ok(vec![State { offset: offset + increment, captures }])
     }
-}
 
-fn special_match(
-    rules: &Rules,
-    pat: &PatternMatcher,
-    offset: usize,
-    result: &mut Match,
-) -> MResult<Matched> {
-    let ty = pat.ty.as_str();
-    let name = pat.get_name();
-    let span = result.tts[offset].span();
-
-    if let Some(pattern_set) = rules.matches.get(ty) {
-        let mut next_result = Match::new(result.tts[offset..].to_vec());
-        let mut errors = Vec::new();
-
-        for Patterns(patterns) in pattern_set {
-            let increment = match match_patterns_impl(rules, patterns, 0, &mut next_result)? {
-                Matched::Success { offset } => offset,
-                Matched::Failed { error } => {
-                    errors.push(error);
-                    continue;
-                }
-            };
-            next_result.tts.drain(increment..);
-            groupify(&mut next_result.tts);
-            result.nest(name, next_result);
-            return Ok(Matched::Success { offset: offset + increment });
+    /// Returns the most preferred state that matched all of `tts`
+    fn complete(&mut self, tts: &[TokenTree], states: Vec<State>) -> Option<State> {
+        let mut complete = None;
+        for state in states {
+            if state.offset == tts.len() {
+                complete = Some(state);
+                break;
+            }
+            self.fail(tts, state.offset, None, None);
         }
+        complete
+    }
 
-        let &PatternError { last_offset, span, .. } =
-            errors.iter().max_by_key(|e| e.last_offset).expect("errors can't be empty here");
-        errors.retain(|e| e.last_offset == last_offset);
-
-        return Ok(Matched::Failed {
-            error: PatternError {
-                pattern: name.to_string(),
-                last_offset,
-                span,
-                reasons: Some(errors),
-            },
-        });
+    fn step(&mut self, tts: &[TokenTree], offset: usize, cost: usize) -> MResult<()> {
+        self.steps += cost;
+        let budget = self.rules.options.step_budget;
+        if self.steps > budget {
+            bail!(
+                "pattern matching exceeded the step budget of {budget}, \
+                you can increase it with `#![step_budget = ...]`" => span_at(tts, offset)
+            );
+        }
+        Ok(())
     }
 
-    let (tt, increment) = match ty {
-        "tt" => match result.tts.get(offset) {
-            Some(tt) => (tt.clone(), 1),
-            None => {
-                return Ok(Matched::Failed {
-                    error: PatternError::new(pat.to_string(), span, offset),
-                });
-            }
-        },
-        "literal" => match result.tts.get(offset) {
-            Some(TokenTree::Literal(lit)) => (lit.clone().into(), 1),
-            _ => {
-                return Ok(Matched::Failed {
-                    error: PatternError::new(pat.to_string(), span, offset),
-                });
-            }
-        },
-        "ident" => match result.tts.get(offset) {
-            Some(TokenTree::Ident(ident)) => (ident.clone().into(), 1),
-            _ => {
-                return Ok(Matched::Failed {
-                    error: PatternError::new(pat.to_string(), span, offset),
-                });
-            }
-        },
-        "lifetime" => match (result.tts.get(offset), result.tts.get(offset + 1)) {
-            (Some(tt1 @ TokenTree::Punct(p)), Some(tt2 @ TokenTree::Ident(_)))
-                if p.as_char() == '\'' && p.spacing() == Spacing::Joint =>
-            {
-                let stream = TokenStream::from_iter([tt1.clone(), tt2.clone()]);
-                (Group::new(Delimiter::None, stream).into(), 2)
-            }
-            _ => {
-                return Ok(Matched::Failed {
-                    error: PatternError::new(pat.to_string(), span, offset),
-                });
+    fn fail(
+        &mut self,
+        tts: &[TokenTree],
+        offset: usize,
+        pattern: Option<String>,
+        expected: Option<&'static str>,
+    ) {
+        let mut position = self.group_offsets.clone();
+        position.push(offset);
+        let error = PatternError { pattern, position, span: span_at(tts, offset), expected };
+
+        match self.errors.first().map(|e| error.position.cmp(&e.position)) {
+            Some(Ordering::Less) => {}
+            Some(Ordering::Equal) => {
+                let message = error.to_string();
+                if self.errors.iter().all(|e| e.to_string() != message) {
+                    self.errors.push(error);
+                }
             }
-        },
-        _ => {
-            let span = result.tts.get(offset).unwrap_or(result.tts.last().unwrap()).span();
-            bail!("unknown matcher `{ty}`" => span)
+            Some(Ordering::Greater) | None => self.errors = vec![error],
         }
-    };
+    }
+
+    fn into_error(self) -> MacroError {
+        let span = self.errors.first().map_or(Span::call_site(), |e| e.span);
+        let message = match self.errors.as_slice() {
+            [] => "no pattern matched".to_string(),
+            [error] => error.to_string(),
+            errors => {
+                let mut message = "unmatched patterns, reasons:".to_string();
+                for error in errors {
+                    message.push_str(&format!("\n    - {error}"));
+                }
+                message
+            }
+        };
+        MacroError { message, span, stream: None }
+    }
+}
 
-    result.add_child(name.to_owned(), tt);
+/// Keeps only the first (i.e. most preferred) state for every offset
+fn dedup(states: Vec<State>) -> Vec<State> {
+    let mut seen = HashSet::new();
+    states.into_iter().filter(|state| seen.insert(state.offset)).collect()
+}
 
-    Ok(Matched::Success { offset: offset + increment })
+/// Returns the span of the token at `offset`, or of the last token if `offset` is out of bounds.
+fn span_at(tts: &[TokenTree], offset: usize) -> Span {
+    tts.get(offset).or(tts.last()).map_or(Span::call_site(), |tt| tt.span())
 }
 
 fn groupify(vec: &mut Vec<TokenTree>) {
@@ -1,55 +1,87 @@
+mod fragments;
 mod functions;
 mod matching;
 
 use std::{str::FromStr, time::Instant};
 
 use matching::{Match, match_patterns};
-use proc_macro2::{Group, Ident, Span, TokenStream, TokenTree};
+use proc_macro2::{Delimiter, Group, Ident, Span, TokenStream, TokenTree};
 
 use crate::{
     Rules,
     errors::MResult,
     macros::{
-        Literal,
-        replacement::{Replacement, SpecialReplacement},
+        Literal, MacroRule,
+        replacement::{Condition, ForExpr, MatchBinding, Operand, Replacement, SpecialReplacement},
     },
 };
 
+/// How deeply calls of functions and helper macros may be nested
+const MAX_CALL_DEPTH: usize = 128;
+
+/// Whether `ty` is a built-in matcher, such as `ident`, `expr` or `str`
+pub(crate) fn is_builtin_matcher(ty: &str) -> bool {
+    FRAGMENTS.contains(&ty) || fragments::LiteralKind::from_name(ty).is_some()
+}
+
+/// The fragment specifiers known from `macro_rules!`
+const FRAGMENTS: &[&str] = &[
+    "tt",
+    "literal",
+    "ident",
+    "lifetime",
+    "ty",
+    "expr",
+    "expr_2021",
+    "path",
+    "pat",
+    "pat_param",
+    "stmt",
+    "block",
+    "item",
+    "meta",
+    "attr",
+    "vis",
+];
+
 pub(crate) fn expand_macro(
     name: &str,
     rules: &Rules,
     tokens: TokenStream,
     span: Span,
 ) -> MResult<TokenStream> {
-    if rules.macro_rule.name == name {
-        return expand_macro_rule(rules, tokens, span);
+    if let Some(macro_rule) = rules.macros.iter().find(|m| m.name == name) {
+        return expand_macro_rule(rules, macro_rule, tokens, span);
     }
     bail!("no rule with the name {name} found!" => span);
 }
 
 pub(crate) fn expand_macro_rule(
     rules: &Rules,
+    macro_rule: &MacroRule,
     tokens: TokenStream,
     span: Span,
 ) -> MResult<TokenStream> {
-    eprintln!("expand: {:#?}\n", rules);
+    eprintln!("expand: {:#?}\n", macro_rule);
 
     let matches_start = Instant::now();
-    let matches = match_patterns(rules, &rules.macro_rule.patterns.0, tokens)?;
+    let matches = match_patterns(rules, &macro_rule.patterns.0, tokens)?;
     eprintln!("pattern matching took {:?}", matches_start.elapsed());
     eprintln!("matches: {matches:#?}\n");
 
     let mut result = TokenStream::new();
-    replace_stream(&matches, &rules.macro_rule.replacements, &mut result, span)?;
+    replace_stream(rules, &matches, &macro_rule.replacements, &mut result, span, 0)?;
 
     Ok(result)
 }
 
 fn replace_stream(
+    rules: &Rules,
     matches: &Match,
     replacements: &[Replacement],
     result: &mut TokenStream,
     span: Span,
+    depth: usize,
 ) -> MResult<()> {
     let mut last_if_matched = None;
 
@@ -58,7 +90,7 @@ fn replace_stream(
             Replacement::Group(ast_group) => {
                 let mut inner = TokenStream::new();
                 // We can't use correct span :(
-                replace_stream(matches, &ast_group.content, &mut inner, span)?;
+                replace_stream(rules, matches, &ast_group.content, &mut inner, span, depth)?;
                 let group = Group::new(ast_group.delimiter.into(), inner);
                 result.extend([TokenTree::Group(group)]);
             }
@@ -86,11 +118,21 @@ fn replace_stream(
                     "last" => functions::last(args, matches, span, result)?,
                     "count" => functions::count(args, matches, span, result)?,
                     "concat" => functions::concat(args, matches, span, result)?,
+                    case @ ("snake_case" | "camel_case" | "pascal_case" | "shouty_snake_case"
+                    | "kebab_case") => functions::convert_case(case, args, matches, span, result)?,
                     s => bail!("unknown function `{s}`" => span),
                 },
+                SpecialReplacement::MethodCall { receiver, method, args } => {
+                    // an optional receiver that wasn't matched produces nothing
+                    if let Some(value) = matches.get(receiver) {
+                        let mut arg_tokens = TokenStream::new();
+                        replace_stream(rules, matches, args, &mut arg_tokens, span, depth)?;
+                        call_method(rules, &value, method, arg_tokens, result, span, depth)?;
+                    }
+                }
                 SpecialReplacement::If { condition, body } => {
-                    if matches.find_child(condition).is_some() {
-                        replace_stream(matches, body, result, span)?;
+                    if test_condition(matches, condition, span)? {
+                        replace_stream(rules, matches, body, result, span, depth)?;
                         last_if_matched = Some(true);
                     } else {
                         last_if_matched = Some(false);
@@ -102,8 +144,8 @@ fn replace_stream(
                         if matched {
                             continue;
                         }
-                        if matches.find_child(condition).is_some() {
-                            replace_stream(matches, body, result, span)?;
+                        if test_condition(matches, condition, span)? {
+                            replace_stream(rules, matches, body, result, span, depth)?;
                             last_if_matched = Some(true);
                         } else {
                             last_if_matched = Some(false);
@@ -119,24 +161,81 @@ fn replace_stream(
                         if matched {
                             continue;
                         }
-                        replace_stream(matches, body, result, span)?;
+                        replace_stream(rules, matches, body, result, span, depth)?;
                         last_if_matched = Some(true);
                     } else {
                         bail!("unexpected `else`" => span);
                     }
                     continue;
                 }
-                SpecialReplacement::For { binding, expr, body } => {
-                    if let Some(exprs) = matches.find_child(expr) {
-                        let mut nested_matches = matches.clone();
-                        for e in &exprs {
-                            nested_matches.replace_child(binding, e.clone());
-                            replace_stream(&nested_matches, body, result, span)?;
+                SpecialReplacement::Match { expr, arms } => {
+                    let value = matches.get(expr);
+                    let value = value.as_ref();
+                    let ty = value.and_then(|value| value.ty.as_deref());
+                    let label = value.and_then(|value| value.label.as_deref());
+                    let arm = arms.iter().find(|arm| match &arm.name {
+                        Some(name) => Some(name.as_str()) == ty || Some(name.as_str()) == label,
+                        None => true,
+                    });
+                    let Some(arm) = arm else {
+                        bail!("no arm of `$match {expr:?}` matches `{}`", ty.unwrap_or("nothing") => span);
+                    };
+
+                    if let Some(value) = value
+                        && !arm.rest
+                        && let Some(capture) = value
+                            .children
+                            .keys()
+                            .filter(|&key| !arm.bindings.iter().any(|b| &b.capture == key))
+                            .min()
+                    {
+                        bail!(
+                            "capture `{capture}` is missing in the `$match` arm, \
+                            use `..` to ignore it" => span
+                        );
+                    }
+
+                    let mut nested_matches = matches.clone();
+                    for MatchBinding { capture, binding, span } in &arm.bindings {
+                        if let Some(value) = value
+                            && !value.declared.contains(capture)
+                        {
+                            let ty = value.ty.as_deref().unwrap_or_default();
+                            bail!("`{ty}` has no capture `{capture}`" => *span);
                         }
-                        last_if_matched = Some(!exprs.is_empty());
-                    } else {
-                        last_if_matched = Some(false);
+                        let child = value.and_then(|value| value.children.get(capture));
+                        nested_matches
+                            .children
+                            .insert(binding.clone(), child.cloned().unwrap_or_default());
                     }
+                    replace_stream(rules, &nested_matches, &arm.body, result, span, depth)?;
+                }
+                SpecialReplacement::For { bindings, index, expr, body } => {
+                    let columns = match expr {
+                        ForExpr::Path(path) => vec![matches.items(path)],
+                        ForExpr::Zip(paths) => {
+                            paths.iter().map(|path| matches.items(path)).collect()
+                        }
+                    };
+                    let len = columns.first().map_or(0, Vec::len);
+                    if let Some(column) = columns.iter().find(|column| column.len() != len) {
+                        bail!(
+                            "the arguments of `zip` have different lengths ({len} and {})",
+                            column.len() => span
+                        );
+                    }
+
+                    let mut nested_matches = matches.clone();
+                    for i in 0..len {
+                        for (binding, column) in bindings.iter().zip(&columns) {
+                            nested_matches.insert(binding.clone(), column[i].clone());
+                        }
+                        if let Some(index) = index {
+                            nested_matches.insert_index(index, i, len);
+                        }
+                        replace_stream(rules, &nested_matches, body, result, span, depth)?;
+                    }
+                    last_if_matched = Some(len > 0);
                     continue;
                 }
             },
@@ -146,3 +245,96 @@ fn replace_stream(
 
     Ok(())
 }
+
+fn call_method(
+    rules: &Rules,
+    receiver: &Match,
+    method: &str,
+    args: TokenStream,
+    result: &mut TokenStream,
+    span: Span,
+    depth: usize,
+) -> MResult<()> {
+    if let Some((index, len)) = receiver.iteration {
+        let n = match method {
+            "index" => index,
+            "len" => len,
+            _ => bail!("unknown method `{method}` of a `$for` index" => span),
+        };
+        if !args.is_empty() {
+            bail!("`{method}` doesn't have arguments" => span);
+        }
+        result.extend([TokenTree::Literal(proc_macro2::Literal::usize_unsuffixed(n))]);
+        return Ok(());
+    }
+
+    let ty = receiver.ty.as_deref().unwrap_or_default();
+    let function = rules.functions.iter().find(|f| f.matcher == ty && f.name == method);
+    let Some(function) = function else {
+        bail!("no function `{method}` for matcher `{ty}`" => span);
+    };
+
+    if depth >= MAX_CALL_DEPTH {
+        bail!("recursion limit of {MAX_CALL_DEPTH} reached while calling `{method}`" => span);
+    }
+
+    let mut fn_matches = match_patterns(rules, &function.params.0, args)?;
+    fn_matches.insert(function.matcher.clone(), receiver.clone());
+    replace_stream(rules, &fn_matches, &function.body, result, span, depth + 1)
+}
+
+fn test_condition(matches: &Match, condition: &Condition, span: Span) -> MResult<bool> {
+    Ok(match condition {
+        Condition::Capture(path) => matches.test(path),
+        Condition::Call { receiver, method } if method == "is_empty" => {
+            matches.find_child(receiver).is_none_or(|tts| is_empty(&tts))
+        }
+        Condition::Call { receiver, method } => {
+            let Some((index, len)) = matches.get(receiver).and_then(|value| value.iteration) else {
+                bail!("`{method}` can only be called on the index of a `$for`" => span);
+            };
+            match method.as_str() {
+                "is_first" => index == 0,
+                _ => index + 1 == len,
+            }
+        }
+        Condition::Eq(left, right) => {
+            operand_text(matches, left, span)? == operand_text(matches, right, span)?
+        }
+        Condition::Not(condition) => !test_condition(matches, condition, span)?,
+        Condition::And(left, right) => {
+            test_condition(matches, left, span)? && test_condition(matches, right, span)?
+        }
+        Condition::Or(left, right) => {
+            test_condition(matches, left, span)? || test_condition(matches, right, span)?
+        }
+    })
+}
+
+/// Whether the tokens are empty, ignoring invisible groups such as an empty `$vis`
+fn is_empty(tts: &[TokenTree]) -> bool {
+    tts.iter().all(|tt| match tt {
+        TokenTree::Group(group) if group.delimiter() == Delimiter::None => {
+            is_empty(&group.stream().into_iter().collect::<Vec<_>>())
+        }
+        _ => false,
+    })
+}
+
+/// The tokens of an operand as a string, for comparisons. A capture that didn't match is compared
+/// as empty.
+fn operand_text(matches: &Match, operand: &Operand, span: Span) -> MResult<String> {
+    Ok(match operand {
+        Operand::Capture(path) => {
+            if !matches.declares(path) {
+                bail!("the capture `{path:?}` does not exist" => span);
+            }
+            match matches.get(path) {
+                Some(value) => TokenStream::from_iter(value.tts.iter().cloned()).to_string(),
+                None => String::new(),
+            }
+        }
+        Operand::Ident(ident) => ident.clone(),
+        Operand::Literal(Literal(text)) => text.clone(),
+    })
+}
@@ -0,0 +1,126 @@
+//! Sets of `match` rules that can be imported in `inception::rules!`:
+//!
+//! - The standard grammar, which is imported with `use rust_parser::<version>;`. A version is
+//!   never changed after it has been released, so macros keep working when the Rust syntax
+//!   evolves. New syntax is added in new versions instead.
+//! - `pub match` rules of other `rules!` blocks, which are imported with `use path::to::name;`.
+
+use std::collections::HashMap;
+
+use syn::parse::{ParseStream, Parser};
+
+use crate::macros::{
+    Alternative, MatchRule, UseRule,
+    pattern::{Pattern, PatternGroup, PatternMatcher, Patterns},
+};
+
+mod v1;
+mod v2;
+
+const VERSIONS: &[(&str, &str)] = &[("v1", v1::GRAMMAR), ("v2", v2::GRAMMAR)];
+
+/// Whether `use_rule` imports the standard grammar
+pub(crate) fn is_builtin(use_rule: &UseRule) -> bool {
+    use_rule.path.first().is_some_and(|krate| krate == "rust_parser")
+}
+
+/// Returns the `match` rules of the grammar version imported by `use_rule`.
+pub(crate) fn import(use_rule: &UseRule) -> syn::Result<HashMap<String, Box<[Alternative]>>> {
+    let [krate, version] = use_rule.path.as_slice() else {
+        synerr!(use_rule.span, "expected `use rust_parser::<version>;`");
+    };
+    if krate != "rust_parser" {
+        synerr!(use_rule.span, "unknown grammar `{krate}`, expected `rust_parser`");
+    }
+    let Some(&(_, grammar)) = VERSIONS.iter().find(|(name, _)| name == version) else {
+        let versions = VERSIONS.iter().map(|(name, _)| *name).collect::<Vec<_>>().join(", ");
+        synerr!(use_rule.span, "unknown version `{version}` of `rust_parser`, expected {versions}");
+    };
+
+    let rules = parse_grammar.parse_str(grammar)?;
+    Ok(rules.into_iter().map(|rule| (rule.name, rule.pattern_set)).collect())
+}
+
+fn parse_grammar(input: ParseStream) -> syn::Result<Vec<MatchRule>> {
+    let mut rules = Vec::new();
+    while !input.is_empty() {
+        rules.push(input.parse::<MatchRule>()?);
+    }
+    Ok(rules)
+}
+
+/// Returns the matcher `name` together with all matchers it depends on, so it can be imported
+/// by another `rules!` block. The dependencies are renamed to `name::dependency`, so they can't
+/// conflict with the matchers of the importing block.
+pub(crate) fn export(
+    matches: &HashMap<String, Box<[Alternative]>>,
+    name: &str,
+) -> HashMap<String, Box<[Alternative]>> {
+    let mut names = vec![name];
+    let mut i = 0;
+    while i < names.len() {
+        for alternative in &matches[names[i]] {
+            collect_dependencies(matches, &alternative.patterns.0, &mut names);
+        }
+        i += 1;
+    }
+
+    let rename = |ty: &str| if ty == name { ty.to_string() } else { format!("{name}::{ty}") };
+    names
+        .iter()
+        .map(|&ty| {
+            let pattern_set = matches[ty]
+                .iter()
+                .map(|Alternative { label, patterns: Patterns(patterns), span }| Alternative {
+                    label: label.clone(),
+                    patterns: Patterns(rename_all(matches, patterns, &rename)),
+                    span: *span,
+                })
+                .collect();
+            (rename(ty), pattern_set)
+        })
+        .collect()
+}
+
+fn collect_dependencies<'a>(
+    matches: &'a HashMap<String, Box<[Alternative]>>,
+    patterns: &[Pattern],
+    names: &mut Vec<&'a str>,
+) {
+    for pattern in patterns {
+        match pattern {
+            Pattern::Group(group) => collect_dependencies(matches, &group.content, names),
+            Pattern::Matcher(matcher) => {
+                if let Some((ty, _)) = matches.get_key_value(&matcher.ty)
+                    && !names.contains(&ty.as_str())
+                {
+                    names.push(ty);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn rename_all(
+    matches: &HashMap<String, Box<[Alternative]>>,
+    patterns: &[Pattern],
+    rename: &impl Fn(&str) -> String,
+) -> Box<[Pattern]> {
+    patterns
+        .iter()
+        .map(|pattern| match pattern {
+            Pattern::Group(PatternGroup { delimiter, content }) => Pattern::Group(PatternGroup {
+                delimiter: *delimiter,
+                content: rename_all(matches, content, rename),
+            }),
+            Pattern::Matcher(matcher) if matches.contains_key(&matcher.ty) => {
+                // the capture keeps its name
+                let name = Some(matcher.get_name().to_string());
+                let ty = rename(&matcher.ty);
+                Pattern::Matcher(PatternMatcher { name, ty, repeat: matcher.repeat.clone() })
+            }
+            pattern => pattern.clone(),
+        })
+        .collect()
+}
@@ -0,0 +1,52 @@
+//! Version 2 of the standard grammar, which adds labels to the alternatives of v1. Do not modify
+//! it, add a new version instead.
+
+pub(super) const GRAMMAR: &str = r#"
+    match struct as
+        | Named($meta:attr* $vis struct $name:ident $generics:generic_params? $where_clause?
+            $body:struct_body)
+        | Tuple($meta:attr* $vis struct $name:ident $generics:generic_params?
+            $body:tuple_struct_body $where_clause? ;)
+        | Unit($meta:attr* $vis struct $name:ident $generics:generic_params? $where_clause? ;);
+
+    match enum as
+        ($meta:attr* $vis enum $name:ident $generics:generic_params? $where_clause?
+            $body:enum_body);
+
+    match union as
+        ($meta:attr* $vis union $name:ident $generics:generic_params? $where_clause?
+            $body:struct_body);
+
+    match struct_body as ({ $fields:{struct_field , .. ,}? });
+    match tuple_struct_body as (( $fields:{tuple_struct_field , .. ,}? ));
+    match struct_field as ($meta:attr* $vis $name:ident $: $ty);
+    match tuple_struct_field as ($meta:attr* $vis $ty);
+
+    match enum_body as ({ $variants:{variant , .. ,}? });
+    match variant as
+        | Named($meta:attr* $name:ident $body:struct_body $discriminant?)
+        | Tuple($meta:attr* $name:ident $body:tuple_struct_body $discriminant?)
+        | Unit($meta:attr* $name:ident $discriminant?);
+    match discriminant as (= $expr);
+
+    match generic_params as (< $params:{generic_param , .. ,}? >);
+    match generic_param as
+        | Const($meta:attr* const $name:ident $: $ty $default:const_default?)
+        | Lifetime($meta:attr* $lifetime $bounds:lifetime_bounds?)
+        | Type($meta:attr* $name:ident $bounds:type_bounds? $default:type_default?);
+    match const_default as (= $tt);
+    match type_default as (= $ty);
+
+    match type_bounds as (: ${bound + ..}?);
+    match lifetime_bounds as (: ${lifetime + ..}?);
+    match bound as
+        | Lifetime($lifetime)
+        | Maybe(? $path)
+        | HigherRanked(for < ${lifetime , .. ,}? > $path)
+        | Trait($path);
+
+    match where_clause as (where $predicates:{where_predicate , .. ,}?);
+    match where_predicate as
+        | Lifetime($lifetime $bounds:lifetime_bounds)
+        | Type($ty $bounds:type_bounds);
+"#;
@@ -10,6 +10,11 @@ macro_rules! t {
             ::proc_macro2::Delimiter::Parenthesis, ::proc_macro2::TokenStream::from_iter([ $($e),* ])
         ))
     };
+    (brackets( $($e:expr),* $(,)? )) => {
+        ::proc_macro2::TokenTree::Group(::proc_macro2::Group::new(
+            ::proc_macro2::Delimiter::Bracket, ::proc_macro2::TokenStream::from_iter([ $($e),* ])
+        ))
+    };
     (braces( $($e:expr),* $(,)? )) => {
         ::proc_macro2::TokenTree::Group(::proc_macro2::Group::new(
             ::proc_macro2::Delimiter::Brace, ::proc_macro2::TokenStream::from_iter([ $($e),* ])
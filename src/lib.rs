@@ -1,9 +1,12 @@
 extern crate proc_macro;
-use std::{collections::HashMap, sync::Mutex, time::Instant};
+use std::{
+    hash::{DefaultHasher, Hash, Hasher},
+    time::Instant,
+};
 
 use errors::{MResult, MacroError};
 use expand::expand_macro;
-use macros::{DeriveArgs, Expand, Rules};
+use macros::{DeriveArgs, Expand, Rules, RulesInput, UseRule, Vis};
 use proc_macro::TokenStream;
 use proc_macro2::{Delimiter, Group, Span, TokenStream as TokenStream2, TokenTree};
 
@@ -11,36 +14,107 @@ use proc_macro2::{Delimiter, Group, Span, TokenStream as TokenStream2, TokenTree
 mod errors;
 #[macro_use]
 mod helper;
+mod encoding;
 mod expand;
+mod grammar;
 mod macros;
 mod old;
 
-static RULES: Mutex<Option<HashMap<String, Vec<Rules>>>> = Mutex::new(None);
-
 #[proc_macro]
 pub fn rules(tokens: TokenStream) -> TokenStream {
     let start = Instant::now();
-    let rules = syn::parse_macro_input!(tokens as Rules);
+    let source = TokenStream2::from(tokens.clone());
+    let (rules, exports) = match syn::parse_macro_input!(tokens as RulesInput) {
+        RulesInput::Rules { rules, exports } => (rules, exports),
+        RulesInput::Import(use_rule) => return import_matcher(&use_rule, source).into(),
+        RulesInput::GlobImport { name, globs } => {
+            return import_glob_matcher(&name, &globs, source).into();
+        }
+    };
     eprintln!("parsing rules took {:?}", start.elapsed());
 
-    let name = rules.macro_rule.name.clone();
+    let span = Span::mixed_site();
+    let mut output = TokenStream2::new();
 
-    let mut rules_guard = RULES.lock().unwrap();
-    let rules_map = rules_guard.get_or_insert_default();
+    // every macro gets its own forwarder, which embeds the rules, so `expand` doesn't depend on
+    // any state that might not survive a restart of the proc-macro server
+    for macro_rule in &rules.macros {
+        let name = &macro_rule.name;
+        let encoded = encoding::encode_rules(&rules, name);
+        eprintln!("rules: {name}");
 
-    let entry = rules_map.entry(rules.macro_rule.name.clone()).or_default();
-    let index = entry.len();
-    entry.push(rules);
+        output.extend([
+            t!["macro_rules", span],
+            t!['!'],
+            t![name, span],
+            t![braces(
+                t![parentheses(
+                    t!['$'],
+                    t![parentheses(t!['$'], t!["t", span], t![':'], t!["tt", span])],
+                    t!['*']
+                )],
+                t!['=' joint],
+                t!['>'],
+                t![braces(
+                    t![':' joint],
+                    t![':'],
+                    t!["inception", span],
+                    t![':' joint],
+                    t![':'],
+                    t!["expand", span],
+                    t!['!'],
+                    t![braces(
+                        t![name, span],
+                        TokenTree::Group(Group::new(Delimiter::Brace, encoded)),
+                        t![braces(t!['$'], t![parentheses(t!['$' joint], t!["t", span])], t!['*'])],
+                    )],
+                )],
+                t![';'],
+            )],
+        ]);
+        if macro_rule.vis == Vis::Public {
+            output.extend(reexport(name, span));
+        }
+    }
 
-    let span = Span::mixed_site();
-    eprintln!("rules: {} v{index}", name);
+    // every `pub match` gets a macro that passes the matcher to the `rules!` blocks importing it,
+    // either explicitly or with a glob import
+    for name in &exports {
+        let encoded = encoding::encode_matches(&grammar::export(&rules.matches, name));
+        output.extend(grammar_macro(&format!("__inception_match_{name}"), encoded, span));
+    }
+
+    output.into()
+}
+
+/// Generates a macro that calls `rules!` again with the encoded matchers prepended:
+///
+/// `name! { [path::to::name] ... }` expands to `rules! { @import [path::to::name] {...} ... }`
+///
+/// The macro is exported, so it can be used by other crates. Since exported macros live in the
+/// crate root, it gets a unique name and is re-exported in the current module. The name depends
+/// on the call site, since identical `pub match` rules can exist in several modules.
+fn grammar_macro(name: &str, encoded: TokenStream2, span: Span) -> TokenStream2 {
+    let mut hasher = DefaultHasher::new();
+    encoded.to_string().hash(&mut hasher);
+    format!("{:?}", Span::call_site()).hash(&mut hasher);
+    let unique_name = format!("{name}_{:016x}", hasher.finish());
 
     TokenStream2::from_iter([
+        t!['#'],
+        t![brackets(t!["doc", span], t![parentheses(t!["hidden", span])])],
+        t!['#'],
+        t![brackets(t!["macro_export", span])],
         t!["macro_rules", span],
         t!['!'],
-        t![&name, span],
+        t![&unique_name, span],
         t![braces(
             t![parentheses(
+                t![brackets(
+                    t!['$'],
+                    t![parentheses(t!['$'], t!["path", span], t![':'], t!["tt", span])],
+                    t!['*']
+                )],
                 t!['$'],
                 t![parentheses(t!['$'], t!["t", span], t![':'], t!["tt", span])],
                 t!['*']
@@ -53,38 +127,151 @@ pub fn rules(tokens: TokenStream) -> TokenStream {
                 t!["inception", span],
                 t![':' joint],
                 t![':'],
-                t!["expand", span],
+                t!["rules", span],
                 t!['!'],
                 t![braces(
-                    t![&name, span],
-                    t![usize index],
-                    t![braces(t!['$'], t![parentheses(t!['$' joint], t!["t", span])], t!['*'])],
+                    t!['@'],
+                    t!["import", span],
+                    t![brackets(
+                        t!['$'],
+                        t![parentheses(t!['$' joint], t!["path", span])],
+                        t!['*']
+                    )],
+                    TokenTree::Group(Group::new(Delimiter::Brace, encoded)),
+                    t!['$'],
+                    t![parentheses(t!['$' joint], t!["t", span])],
+                    t!['*'],
                 )],
             )],
             t![';'],
         )],
+        t!['#'],
+        t![brackets(t!["doc", span], t![parentheses(t!["hidden", span])])],
+        t!["pub", span],
+        t!["use", span],
+        t![&unique_name, span],
+        t!["as", span],
+        t![name, span],
+        t![';'],
     ])
-    .into()
 }
 
-#[doc(hidden)]
-#[proc_macro]
-pub fn expand(tokens: TokenStream) -> TokenStream {
-    let Expand { name, name_span, index, input, span } = syn::parse_macro_input!(tokens as Expand);
+/// `pub(crate) use name;`
+fn reexport(name: &str, span: Span) -> [TokenTree; 5] {
+    [t!["pub", span], t![parentheses(t!["crate", span])], t!["use", span], t![name, span], t![';']]
+}
 
-    let mut rules_guard = RULES.lock().unwrap();
-    let rules_map = rules_guard.get_or_insert_default();
+/// Invokes the macro generated for the imported `pub match`, which calls `rules!` again with the
+/// encoded matchers prepended:
+///
+/// `path::to::__inception_match_name! { [path::to::name] ... }`
+fn import_matcher(use_rule: &UseRule, source: TokenStream2) -> TokenStream2 {
+    let (name, prefix) = use_rule.path.split_last().unwrap();
+    invoke_matcher(prefix, name, &use_rule.path, source, use_rule.span)
+}
 
-    eprintln!("expand: {name} v{index}");
+/// Requests a matcher from the glob imports, which provide it as `@import [name]`. With several
+/// globs, a hidden module re-exports all of them, so the compiler resolves the macro of the
+/// matcher and reports an error if more than one glob provides it:
+///
+/// `mod __inception_glob_name_hash { pub(super) use path::to::*; ... }`
+fn import_glob_matcher(name: &str, globs: &[UseRule], source: TokenStream2) -> TokenStream2 {
+    let provided = [name.to_string()];
+    if let [glob] = globs {
+        let prefix = &glob.path[..glob.path.len() - 1];
+        return invoke_matcher(prefix, name, &provided, source, glob.span);
+    }
 
-    let Some(macros) = rules_map.get(&name) else {
-        synbail!(name_span, "unknown inception macro");
-    };
-    let Some(rules) = macros.get(index) else {
-        synbail!(name_span, "unknown inception macro version {index} of macro {name}");
+    let mut hasher = DefaultHasher::new();
+    for glob in globs {
+        glob.path.hash(&mut hasher);
+    }
+    format!("{:?}", Span::call_site()).hash(&mut hasher);
+    let module = format!("__inception_glob_{name}_{:016x}", hasher.finish());
+
+    // the items of the parent module are imported, so paths to its child modules still work
+    let span = globs[0].span;
+    let mut content = vec![
+        t!['#'],
+        t!['!'],
+        t![brackets(t!["allow", span], t![parentheses(t!["unused_imports", span])])],
+        t!["use", span],
+        t!["super", span],
+        t![':' joint],
+        t![':'],
+        t!['*'],
+        t![';'],
+    ];
+    for glob in globs {
+        // the paths are relative to the parent module
+        let mut path = glob.path.iter().map(String::as_str).collect::<Vec<_>>();
+        match path[0] {
+            "self" => path[0] = "super",
+            "super" => path.insert(0, "super"),
+            _ => {}
+        }
+
+        let span = glob.span;
+        content.extend([t!["pub", span], t![parentheses(t!["super", span])], t!["use", span]]);
+        for (i, segment) in path.into_iter().enumerate() {
+            if i > 0 {
+                content.extend([t![':' joint], t![':']]);
+            }
+            content.push(if segment == "*" { t!['*'] } else { t![segment, span] });
+        }
+        content.push(t![';']);
+    }
+
+    let mut output = TokenStream2::from_iter([
+        t!['#'],
+        t![brackets(t!["doc", span], t![parentheses(t!["hidden", span])])],
+        t!["mod", span],
+        t![&module, span],
+        TokenTree::Group(Group::new(Delimiter::Brace, content.into_iter().collect())),
+    ]);
+    output.extend(invoke_matcher(&[module], name, &provided, source, span));
+    output
+}
+
+/// `prefix::__inception_match_name! { [provided] ... }`
+fn invoke_matcher(
+    prefix: &[String],
+    name: &str,
+    provided: &[String],
+    source: TokenStream2,
+    span: Span,
+) -> TokenStream2 {
+    let path_tokens = |segments: &[String]| {
+        let mut tokens = Vec::new();
+        for (i, segment) in segments.iter().enumerate() {
+            if i > 0 {
+                tokens.extend([t![':' joint], t![':']]);
+            }
+            tokens.push(t![segment, span]);
+        }
+        tokens
     };
 
-    match expand_macro(&name, rules, input, span) {
+    let path = TokenStream2::from_iter(path_tokens(provided));
+    let mut input =
+        TokenStream2::from_iter([TokenTree::Group(Group::new(Delimiter::Bracket, path))]);
+    input.extend(source);
+
+    let mut macro_path = prefix.to_vec();
+    macro_path.push(format!("__inception_match_{name}"));
+    let mut output = TokenStream2::from_iter(path_tokens(&macro_path));
+    output.extend([t!['!'], TokenTree::Group(Group::new(Delimiter::Brace, input))]);
+    output
+}
+
+#[doc(hidden)]
+#[proc_macro]
+pub fn expand(tokens: TokenStream) -> TokenStream {
+    let Expand { name, rules, input, span } = syn::parse_macro_input!(tokens as Expand);
+
+    eprintln!("expand: {name}");
+
+    match expand_macro(&name, &rules, input, span) {
         Ok(output) => output.into(),
         Err(MacroError { message, span, stream: Some(stream) }) => {
             errors::error_with(&message, span, stream).into()
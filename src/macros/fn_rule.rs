@@ -0,0 +1,49 @@
+use syn::{
+    ext::IdentExt,
+    parse::{Parse, ParseStream},
+};
+
+use super::{
+    pattern::Patterns,
+    replacement::{Replacement, Replacements},
+};
+
+/// A replacement function attached to a matcher: `fn generic_params.get_names() { ... }`
+///
+/// It is called with `${s.generics.get_names()}`. In the body, the receiver is bound to the name
+/// of the matcher. Helper macros like `macro struct_body.prefixed($prefix:ident) { ... }` also
+/// match their arguments, e.g. `${s.body.prefixed(${s.name})}`.
+#[derive(Debug)]
+pub(crate) struct FnRule {
+    pub(crate) matcher: String,
+    pub(crate) name: String,
+    pub(crate) params: Patterns,
+    pub(crate) body: Box<[Replacement]>,
+}
+
+impl Parse for FnRule {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let is_macro = input.peek(syn::Token![macro]);
+        if is_macro {
+            input.parse::<syn::Token![macro]>()?;
+        } else {
+            input.parse::<syn::Token![fn]>()?;
+        }
+        let matcher = input.call(syn::Ident::parse_any)?.to_string();
+        input.parse::<syn::Token![.]>()?;
+        let name = input.parse::<syn::Ident>()?.to_string();
+
+        let params_input;
+        let paren = syn::parenthesized!(params_input in input);
+        let params = params_input.parse::<Patterns>()?;
+        if !is_macro && !params.0.is_empty() {
+            synerr!(paren.span.join(), "functions can't have parameters, use a helper macro");
+        }
+
+        let body;
+        syn::braced!(body in input);
+        let Replacements(body) = body.parse()?;
+
+        Ok(FnRule { matcher, name, params, body })
+    }
+}
@@ -1,6 +1,6 @@
 use core::fmt;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub(crate) struct Literal(pub(crate) String);
 
 impl From<proc_macro2::Literal> for Literal {
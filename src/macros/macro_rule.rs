@@ -1,13 +1,14 @@
 use syn::parse::{Parse, ParseStream};
 
 use super::{
+    Vis,
     pattern::Patterns,
     replacement::{Replacement, Replacements},
 };
 
 #[derive(Debug)]
 pub(crate) struct MacroRule {
-    // pub(crate) vis: Vis,
+    pub(crate) vis: Vis,
     pub(crate) name: String,
     pub(crate) patterns: Patterns,
     pub(crate) replacements: Box<[Replacement]>,
@@ -27,6 +28,6 @@ impl Parse for MacroRule {
         _ = syn::braced!(replacement_input in input);
         let Replacements(replacements) = replacement_input.parse()?;
 
-        Ok(MacroRule { name, patterns, replacements })
+        Ok(MacroRule { vis: Vis::Private, name, patterns, replacements })
     }
 }
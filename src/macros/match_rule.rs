@@ -1,15 +1,35 @@
+use proc_macro2::Span;
 use syn::{
     ext::IdentExt,
     parse::{Parse, ParseStream},
 };
 
-use super::pattern::Patterns;
+use super::{Vis, pattern::Patterns};
 
 #[derive(Debug)]
 pub(crate) struct MatchRule {
-    // pub(crate) vis: Vis,
+    pub(crate) vis: Vis,
     pub(crate) name: String,
-    pub(crate) pattern_set: Box<[Patterns]>,
+    pub(crate) pattern_set: Box<[Alternative]>,
+}
+
+/// An alternative of a `match` rule, optionally with a label: `Label($foo $bar)`
+#[derive(Debug, Clone)]
+pub(crate) struct Alternative {
+    pub(crate) label: Option<String>,
+    pub(crate) patterns: Patterns,
+    /// Where the alternative is declared. Isn't encoded, so it's the call site for imported rules.
+    pub(crate) span: Span,
+}
+
+impl Alternative {
+    /// Describes the alternative with the given index in diagnostics
+    pub(crate) fn describe(&self, index: usize) -> String {
+        match &self.label {
+            Some(label) => format!("alternative `{label}`"),
+            None => format!("alternative {}", index + 1),
+        }
+    }
 }
 
 impl Parse for MatchRule {
@@ -21,17 +41,31 @@ impl Parse for MatchRule {
 
         _ = input.parse::<syn::Token![|]>();
 
-        let mut patterns = vec![];
+        let mut patterns = Vec::<Alternative>::new();
         loop {
+            let label = input.parse::<Option<syn::Ident>>()?;
+            let label_span = label.as_ref().map(syn::Ident::span);
+            let label = match label {
+                Some(label) => {
+                    let label_str = label.to_string();
+                    if patterns.iter().any(|alt| alt.label.as_ref() == Some(&label_str)) {
+                        synerr!(label.span(), "duplicate label `{label}` in matcher {name}");
+                    }
+                    Some(label_str)
+                }
+                None => None,
+            };
+
             let group;
-            syn::parenthesized!(group in input);
-            patterns.push(group.parse::<Patterns>()?);
+            let paren = syn::parenthesized!(group in input);
+            let span = label_span.unwrap_or(paren.span.open());
+            patterns.push(Alternative { label, patterns: group.parse::<Patterns>()?, span });
             if input.parse::<syn::Token![|]>().is_err() {
                 break;
             }
         }
 
         input.parse::<syn::Token![;]>()?;
-        Ok(MatchRule { name, pattern_set: patterns.into_boxed_slice() })
+        Ok(MatchRule { vis: Vis::Private, name, pattern_set: patterns.into_boxed_slice() })
     }
 }
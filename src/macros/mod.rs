@@ -1,12 +1,16 @@
 mod delimiter;
 mod derive_args;
 mod expand;
+mod fn_rule;
 mod literal;
 mod macro_rule;
 mod match_rule;
+mod options;
 mod path;
 mod punct;
 mod rule;
+mod shadowing;
+mod use_rule;
 mod visibility;
 
 pub(super) mod pattern;
@@ -15,10 +19,13 @@ pub(super) mod replacement;
 pub(crate) use delimiter::Delimiter;
 pub(crate) use derive_args::DeriveArgs;
 pub(crate) use expand::Expand;
+pub(crate) use fn_rule::FnRule;
 pub(crate) use literal::Literal;
 pub(crate) use macro_rule::MacroRule;
-pub(crate) use match_rule::MatchRule;
+pub(crate) use match_rule::{Alternative, MatchRule};
+pub(crate) use options::Options;
 pub(crate) use path::Path;
 pub(crate) use punct::Punct;
-pub(crate) use rule::Rules;
+pub(crate) use rule::{Rules, RulesInput};
+pub(crate) use use_rule::UseRule;
 pub(crate) use visibility::Vis;
@@ -0,0 +1,46 @@
+use syn::{
+    Attribute,
+    parse::{Parse, ParseStream},
+};
+
+/// Settings of an `inception::rules!` block, which are declared as inner attributes:
+///
+/// - `#![step_budget = 100000]`: the number of steps after which pattern matching is aborted
+/// - `#![deny_ambiguity]`: report an error when several alternatives of a `match` rule match the
+///   same input
+#[derive(Debug)]
+pub(crate) struct Options {
+    pub(crate) step_budget: usize,
+    pub(crate) deny_ambiguity: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options { step_budget: 100_000, deny_ambiguity: false }
+    }
+}
+
+impl Parse for Options {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut options = Options::default();
+
+        for attr in input.call(Attribute::parse_inner)? {
+            let span = attr.bracket_token.span.join();
+            if attr.path().is_ident("step_budget") {
+                let value = attr.meta.require_name_value()?;
+                let syn::Expr::Lit(syn::ExprLit { lit: syn::Lit::Int(lit), .. }) = &value.value
+                else {
+                    synerr!(span, "expected an integer");
+                };
+                options.step_budget = lit.base10_parse()?;
+            } else if attr.path().is_ident("deny_ambiguity") {
+                attr.meta.require_path_only()?;
+                options.deny_ambiguity = true;
+            } else {
+                synerr!(span, "unknown option");
+            }
+        }
+
+        Ok(options)
+    }
+}
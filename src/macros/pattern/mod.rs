@@ -15,6 +15,7 @@ mod pattern_matcher;
 mod patterns;
 mod repeat;
 
+#[derive(Clone)]
 pub(crate) enum Pattern {
     Group(PatternGroup),
     Ident(String),
@@ -4,7 +4,7 @@ use crate::macros::Delimiter;
 
 use super::Pattern;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub(crate) struct PatternGroup {
     pub(crate) delimiter: Delimiter,
     pub(crate) content: Box<[Pattern]>,
@@ -2,7 +2,7 @@ use core::fmt::{self, Write};
 
 use syn::{Ident, ext::IdentExt, parse::ParseStream};
 
-use crate::{helper::DebugToDisplay, macros::Punct};
+use crate::helper::DebugToDisplay;
 
 use super::{Interspersed, Quantifier, Repeat, RepeatKind};
 
@@ -17,6 +17,7 @@ use super::{Interspersed, Quantifier, Repeat, RepeatKind};
 /// - optional repetitions with interspersed punctuation: `$foo:{bar | ..}?`
 /// - shorthands: `$bar`, `$bar*`, `${...}`, `${...}?`
 /// - escape: `$$`, `$+`, `$*`, `$?`, `$:`
+#[derive(Clone)]
 pub(crate) struct PatternMatcher {
     pub(crate) name: Option<String>,
     pub(crate) ty: String,
@@ -28,24 +29,6 @@ impl PatternMatcher {
         self.name.as_deref().unwrap_or(&self.ty)
     }
 
-    pub(crate) fn quantifier(&self) -> Option<Quantifier> {
-        self.repeat.as_ref().map(|r| r.quantifier)
-    }
-
-    pub(crate) fn leading_punct(&self) -> Option<Punct> {
-        self.repeat
-            .as_ref()
-            .and_then(|r| r.interspersed)
-            .and_then(|i| if let RepeatKind::Leading = i.kind { Some(i.punct) } else { None })
-    }
-
-    pub(crate) fn trailing_punct(&self) -> Option<Punct> {
-        self.repeat
-            .as_ref()
-            .and_then(|r| r.interspersed)
-            .and_then(|i| if let RepeatKind::Trailing = i.kind { Some(i.punct) } else { None })
-    }
-
     pub(super) fn parse_after_dollar(input: ParseStream) -> syn::Result<PatternMatcher> {
         // TODO: use `peek2` to simplify this logic
 
@@ -130,12 +113,12 @@ impl fmt::Debug for PatternMatcher {
 }
 
 fn handle_question_mark(input: ParseStream, matcher: &mut PatternMatcher) {
-    if input.peek(syn::Token![?]) {
-        if let Some(repeat) = &mut matcher.repeat {
-            // ${x + ..}?
-            _ = input.parse::<syn::Token![?]>();
-            repeat.quantifier = Quantifier::Star;
-        }
+    if input.peek(syn::Token![?])
+        && let Some(repeat) = &mut matcher.repeat
+    {
+        // ${x + ..}?
+        _ = input.parse::<syn::Token![?]>();
+        repeat.quantifier = Quantifier::Star;
     }
 }
 
@@ -168,15 +151,15 @@ fn parse_braced_group(input: ParseStream, name: Option<Ident>) -> syn::Result<Pa
 
     let trailing = input.parse::<proc_macro2::Punct>().ok();
 
-    if let Some(leading) = &leading {
-        if leading.as_char() != middle.as_char() {
-            synerr!(middle.span(), "expected punctuations to be equal");
-        }
+    if let Some(leading) = &leading
+        && leading.as_char() != middle.as_char()
+    {
+        synerr!(middle.span(), "expected punctuations to be equal");
     }
-    if let Some(trailing) = &trailing {
-        if trailing.as_char() != middle.as_char() {
-            synerr!(trailing.span(), "expected punctuations to be equal");
-        }
+    if let Some(trailing) = &trailing
+        && trailing.as_char() != middle.as_char()
+    {
+        synerr!(trailing.span(), "expected punctuations to be equal");
     }
     let kind = match (leading, trailing) {
         (Some(_), Some(trailing)) => {
@@ -4,6 +4,7 @@ use syn::parse::{Parse, ParseStream};
 
 use super::Pattern;
 
+#[derive(Clone)]
 pub(crate) struct Patterns(pub(crate) Box<[Pattern]>);
 
 impl fmt::Debug for Patterns {
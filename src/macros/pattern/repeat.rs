@@ -2,7 +2,7 @@ use core::fmt::{self, Write};
 
 use crate::{helper::DebugToDisplay, macros::Punct};
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub(crate) struct Repeat {
     pub(crate) quantifier: Quantifier,
     pub(crate) interspersed: Option<Interspersed>,
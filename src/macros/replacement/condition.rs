@@ -0,0 +1,123 @@
+use proc_macro2::Span;
+use syn::parse::{Parse, ParseStream};
+
+use crate::macros::{Literal, Path};
+
+/// The condition of an `$if` or `$else if`
+#[derive(Debug)]
+pub(crate) enum Condition {
+    /// `s.name`: whether the capture exists
+    Capture(Path),
+    /// `s.fields.is_empty()`, or `i.is_first()` or `i.is_last()`, where `i` is the index of a `$for`
+    Call { receiver: Path, method: String },
+    /// `$s.name == Foo`: whether the tokens are the same
+    Eq(Operand, Operand),
+    /// `!condition`
+    Not(Box<Condition>),
+    /// `a && b`
+    And(Box<Condition>, Box<Condition>),
+    /// `a || b`
+    Or(Box<Condition>, Box<Condition>),
+}
+
+/// An operand of `==` or `!=`
+#[derive(Debug)]
+pub(crate) enum Operand {
+    /// `$s.name`: the tokens of a capture
+    Capture(Path),
+    /// `Foo`: an identifier
+    Ident(String),
+    /// `"foo"` or `2`
+    Literal(Literal),
+}
+
+impl Parse for Operand {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        if input.parse::<Option<syn::Token![$]>>()?.is_some() {
+            return Ok(Operand::Capture(input.parse()?));
+        }
+        if input.cursor().literal().is_some() {
+            let literal = input.parse::<proc_macro2::Literal>()?;
+            return Ok(Operand::Literal(literal.into()));
+        }
+        let span = input.span();
+        let path = input.parse::<Path>()?;
+        ident_operand(path, span)
+    }
+}
+
+/// A bare word in a comparison. Captures must be written with `$`, since a misspelled capture
+/// would otherwise be compared as a word.
+fn ident_operand(mut path: Path, span: Span) -> syn::Result<Operand> {
+    if path.0.len() != 1 {
+        synerr!(span, "expected `${path:?}` to compare the capture");
+    }
+    Ok(Operand::Ident(path.0.pop().unwrap()))
+}
+
+impl Parse for Condition {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut condition = parse_and(input)?;
+        while input.peek(syn::Token![||]) {
+            input.parse::<syn::Token![||]>()?;
+            condition = Condition::Or(Box::new(condition), Box::new(parse_and(input)?));
+        }
+        Ok(condition)
+    }
+}
+
+fn parse_and(input: ParseStream) -> syn::Result<Condition> {
+    let mut condition = parse_unary(input)?;
+    while input.peek(syn::Token![&&]) {
+        input.parse::<syn::Token![&&]>()?;
+        condition = Condition::And(Box::new(condition), Box::new(parse_unary(input)?));
+    }
+    Ok(condition)
+}
+
+fn parse_unary(input: ParseStream) -> syn::Result<Condition> {
+    if input.peek(syn::Token![!]) {
+        input.parse::<syn::Token![!]>()?;
+        return Ok(Condition::Not(Box::new(parse_unary(input)?)));
+    }
+    if input.peek(syn::token::Paren) {
+        let content;
+        syn::parenthesized!(content in input);
+        return content.parse();
+    }
+
+    let span = input.span();
+    if input.peek(syn::Token![$]) || input.cursor().literal().is_some() {
+        let left = input.parse::<Operand>()?;
+        return parse_comparison(input, left, span);
+    }
+
+    let mut path = input.parse::<Path>()?;
+    if input.peek(syn::Token![==]) || input.peek(syn::Token![!=]) {
+        return parse_comparison(input, ident_operand(path, span)?, span);
+    }
+    if !input.peek(syn::token::Paren) {
+        return Ok(Condition::Capture(path));
+    }
+
+    let args;
+    let paren = syn::parenthesized!(args in input);
+    if !args.is_empty() {
+        synerr!(paren.span.join(), "predicates can't have arguments");
+    }
+    let method = path.0.pop().unwrap();
+    if path.0.is_empty() || !matches!(method.as_str(), "is_empty" | "is_first" | "is_last") {
+        synerr!(span, "unknown predicate `{}`", method);
+    }
+    Ok(Condition::Call { receiver: path, method })
+}
+
+fn parse_comparison(input: ParseStream, left: Operand, span: Span) -> syn::Result<Condition> {
+    if input.parse::<Option<syn::Token![==]>>()?.is_some() {
+        return Ok(Condition::Eq(left, input.parse()?));
+    }
+    if input.parse::<Option<syn::Token![!=]>>()?.is_some() {
+        return Ok(Condition::Not(Box::new(Condition::Eq(left, input.parse()?))));
+    }
+    synerr!(span, "expected a comparison");
+}
@@ -1,6 +1,20 @@
-use crate::macros::Path;
+use syn::parse::{Parse, ParseStream};
 
+use crate::macros::{Literal, Path};
+
+/// An argument of a builtin function: a capture or a literal
 #[derive(Debug)]
-pub(crate) struct Expr {
-    pub(crate) path: Path,
+pub(crate) enum Expr {
+    Path(Path),
+    Literal(Literal),
+}
+
+impl Parse for Expr {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        if input.cursor().literal().is_some() {
+            let literal = input.parse::<proc_macro2::Literal>()?;
+            return Ok(Expr::Literal(literal.into()));
+        }
+        Ok(Expr::Path(input.parse()?))
+    }
 }
@@ -0,0 +1,78 @@
+use proc_macro2::Span;
+use syn::{
+    ext::IdentExt,
+    parse::{Parse, ParseStream},
+};
+
+use super::{Replacement, Replacements};
+
+/// An arm of `$match`: `Name { capture, capture: binding, .. } => { ... }` or `_ => { ... }`
+#[derive(Debug)]
+pub(crate) struct MatchArm {
+    /// The matcher type or alternative, or `None` for `_`
+    pub(crate) name: Option<String>,
+    pub(crate) bindings: Box<[MatchBinding]>,
+    /// Whether the arm ends with `..`, so not all captures have to be listed
+    pub(crate) rest: bool,
+    pub(crate) body: Box<[Replacement]>,
+}
+
+/// A capture bound by a `$match` arm: `capture` or `capture: binding`
+#[derive(Debug)]
+pub(crate) struct MatchBinding {
+    pub(crate) capture: String,
+    pub(crate) binding: String,
+    pub(crate) span: Span,
+}
+
+impl Parse for MatchArm {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let name = if input.parse::<Option<syn::Token![_]>>()?.is_some() {
+            None
+        } else {
+            Some(input.call(syn::Ident::parse_any)?.to_string())
+        };
+
+        let mut bindings = Vec::new();
+        let mut rest = name.is_none();
+        if input.peek(syn::token::Brace) {
+            let fields;
+            syn::braced!(fields in input);
+            while !fields.is_empty() {
+                if fields.parse::<Option<syn::Token![..]>>()?.is_some() {
+                    rest = true;
+                    break;
+                }
+                let capture = fields.call(syn::Ident::parse_any)?;
+                let binding = if fields.parse::<Option<syn::Token![:]>>()?.is_some() {
+                    fields.call(syn::Ident::parse_any)?.to_string()
+                } else {
+                    capture.to_string()
+                };
+                bindings.push(MatchBinding {
+                    capture: capture.to_string(),
+                    binding,
+                    span: capture.span(),
+                });
+
+                if fields.parse::<Option<syn::Token![,]>>()?.is_none() {
+                    break;
+                }
+            }
+            if !fields.is_empty() {
+                synerr!(fields.span(), "expected `}}`");
+            }
+        } else if name.is_some() {
+            // without braces, no captures are bound
+            rest = true;
+        }
+
+        input.parse::<syn::Token![=>]>()?;
+        let body;
+        syn::braced!(body in input);
+        let Replacements(body) = body.parse()?;
+        _ = input.parse::<Option<syn::Token![,]>>()?;
+
+        Ok(MatchArm { name, bindings: bindings.into_boxed_slice(), rest, body })
+    }
+}
@@ -3,14 +3,18 @@ use core::fmt;
 use proc_macro2::{Delimiter, Span, TokenTree};
 use syn::parse::{Parse, ParseStream, Parser};
 
+pub(crate) use condition::{Condition, Operand};
 pub(crate) use expr::Expr;
+pub(crate) use match_arm::{MatchArm, MatchBinding};
 pub(crate) use replacement_group::ReplacementGroup;
 pub(crate) use replacements::Replacements;
-pub(crate) use special_replacement::SpecialReplacement;
+pub(crate) use special_replacement::{ForExpr, SpecialReplacement};
 
 use super::{Literal, Path, Punct};
 
+mod condition;
 mod expr;
+mod match_arm;
 mod replacement_group;
 mod replacements;
 mod special_replacement;
@@ -102,7 +106,7 @@ impl ReplacementWithKwSpan {
                 } else if ident == "else" {
                     (SpecialReplacement::parse_after_else(input)?, Some(ident.span()))
                 } else if ident == "match" {
-                    synerr!(ident.span(), "matches are not yet implemented");
+                    (SpecialReplacement::parse_after_match(input)?, Some(ident.span()))
                 } else {
                     (SpecialReplacement::Path(Path(vec![ident.to_string()])), None)
                 }
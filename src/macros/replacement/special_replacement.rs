@@ -2,16 +2,27 @@ use syn::{parse::ParseStream, punctuated::Punctuated};
 
 use crate::macros::Path;
 
-use super::{Replacement, Replacements, expr::Expr};
+use super::{Condition, MatchArm, Replacement, Replacements, expr::Expr};
 
 #[derive(Debug)]
 pub(crate) enum SpecialReplacement {
     Path(Path),
     Call { func: String, args: Box<[Expr]> },
-    If { condition: Path, body: Box<[Replacement]> },
-    ElseIf { condition: Path, body: Box<[Replacement]> },
+    MethodCall { receiver: Path, method: String, args: Box<[Replacement]> },
+    If { condition: Condition, body: Box<[Replacement]> },
+    ElseIf { condition: Condition, body: Box<[Replacement]> },
     Else { body: Box<[Replacement]> },
-    For { binding: String, expr: Path, body: Box<[Replacement]> },
+    For { bindings: Box<[String]>, index: Option<String>, expr: ForExpr, body: Box<[Replacement]> },
+    Match { expr: Path, arms: Box<[MatchArm]> },
+}
+
+/// What a `$for` iterates over
+#[derive(Debug)]
+pub(crate) enum ForExpr {
+    /// The repetitions of a capture
+    Path(Path),
+    /// `zip(a, b, ...)`: the repetitions of several captures in lockstep
+    Zip(Box<[Path]>),
 }
 
 impl SpecialReplacement {
@@ -21,19 +32,24 @@ impl SpecialReplacement {
 
         if input.peek(syn::token::Paren) {
             let parenthesized;
-            let paren = syn::parenthesized!(parenthesized in input);
-
-            let args = Punctuated::<Path, syn::Token![,]>::parse_terminated(&parenthesized)?;
-            let args =
-                args.into_iter().map(|path| Expr { path }).collect::<Vec<_>>().into_boxed_slice();
+            syn::parenthesized!(parenthesized in input);
 
-            if path.0.len() > 1 {
-                synerr!(paren.span.join(), "method calls are not supported");
-            }
             let func_segment = path.0.pop().unwrap();
+            if !path.0.is_empty() {
+                let Replacements(args) = parenthesized.parse()?;
+                return Ok(SpecialReplacement::MethodCall {
+                    receiver: path,
+                    method: func_segment,
+                    args,
+                });
+            }
+
+            let args = Punctuated::<Expr, syn::Token![,]>::parse_terminated(&parenthesized)?;
+            let args = args.into_iter().collect::<Vec<_>>().into_boxed_slice();
 
             match func_segment.as_str() {
-                "first" | "last" | "count" | "concat" => {
+                "first" | "last" | "count" | "concat" | "snake_case" | "camel_case"
+                | "pascal_case" | "shouty_snake_case" | "kebab_case" => {
                     return Ok(SpecialReplacement::Call { func: func_segment, args });
                 }
                 _ => synerr!(span, "unknown function `{}`", func_segment),
@@ -44,20 +60,52 @@ impl SpecialReplacement {
     }
 
     pub(super) fn parse_after_for(input: ParseStream) -> syn::Result<Self> {
-        let ident = input.parse::<syn::Ident>()?;
+        let bindings_span = input.span();
+        let bindings = if input.peek(syn::token::Paren) {
+            let content;
+            syn::parenthesized!(content in input);
+            let bindings = Punctuated::<syn::Ident, syn::Token![,]>::parse_terminated(&content)?;
+            bindings.into_iter().map(|ident| ident.to_string()).collect()
+        } else {
+            vec![input.parse::<syn::Ident>()?.to_string()]
+        };
+        let mut index = None;
+        if input.peek(syn::Token![,]) {
+            input.parse::<syn::Token![,]>()?;
+            index = Some(input.parse::<syn::Ident>()?.to_string());
+        }
         input.parse::<syn::Token![in]>()?;
-        let expr = input.parse::<Path>()?;
+        let path = input.parse::<Path>()?;
+        let expr = if path.0.len() == 1 && path.0[0] == "zip" && input.peek(syn::token::Paren) {
+            let content;
+            syn::parenthesized!(content in input);
+            let paths = Punctuated::<Path, syn::Token![,]>::parse_terminated(&content)?;
+            ForExpr::Zip(paths.into_iter().collect())
+        } else if bindings.len() == 1 {
+            ForExpr::Path(path)
+        } else {
+            synerr!(bindings_span, "multiple bindings require `zip(...)`");
+        };
+        if let ForExpr::Zip(paths) = &expr
+            && paths.len() != bindings.len()
+        {
+            synerr!(
+                bindings_span,
+                "expected {} bindings for `zip`, got {}",
+                paths.len(),
+                bindings.len()
+            );
+        }
 
         let replacements;
         syn::braced!(replacements in input);
         let Replacements(body) = replacements.parse()?;
 
-        let binding = ident.to_string();
-        Ok(SpecialReplacement::For { binding, expr, body })
+        Ok(SpecialReplacement::For { bindings: bindings.into(), index, expr, body })
     }
 
     pub(super) fn parse_after_if(input: ParseStream) -> syn::Result<Self> {
-        let condition = input.parse::<Path>()?;
+        let condition = input.parse::<Condition>()?;
 
         let replacements;
         syn::braced!(replacements in input);
@@ -80,4 +128,17 @@ impl SpecialReplacement {
 
         Ok(SpecialReplacement::Else { body })
     }
+
+    pub(super) fn parse_after_match(input: ParseStream) -> syn::Result<Self> {
+        let expr = input.parse::<Path>()?;
+
+        let arms_input;
+        syn::braced!(arms_input in input);
+        let mut arms = Vec::new();
+        while !arms_input.is_empty() {
+            arms.push(arms_input.parse::<MatchArm>()?);
+        }
+
+        Ok(SpecialReplacement::Match { expr, arms: arms.into_boxed_slice() })
+    }
 }
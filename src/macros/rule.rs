@@ -1,62 +1,219 @@
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
 
 use proc_macro2::Span;
 use syn::parse::{Parse, ParseStream};
 
-use super::{MacroRule, MatchRule, Vis, pattern::Patterns};
+use crate::{encoding, expand, grammar};
+
+use super::{
+    Alternative, FnRule, MacroRule, MatchRule, Options, UseRule, Vis, pattern::Pattern, shadowing,
+};
 
 #[derive(Debug)]
 pub(crate) enum Rule {
     Match(MatchRule),
     Macro(MacroRule),
+    Use(UseRule),
+    Fn(FnRule),
 }
 
 impl Parse for Rule {
     fn parse(input: ParseStream) -> syn::Result<Self> {
-        _ = Vis::parse(input)?;
-        if input.peek(syn::Token![macro]) {
-            input.parse::<MacroRule>().map(Rule::Macro)
+        let vis = Vis::parse(input)?;
+        if input.peek(syn::Token![fn])
+            || input.peek(syn::Token![macro]) && input.peek3(syn::Token![.])
+        {
+            let span = input.span();
+            let rule = input.parse::<FnRule>()?;
+            if vis == Vis::Public {
+                synerr!(span, "functions and helper macros can't be `pub`");
+            }
+            Ok(Rule::Fn(rule))
+        } else if input.peek(syn::Token![macro]) {
+            let mut rule = input.parse::<MacroRule>()?;
+            rule.vis = vis;
+            Ok(Rule::Macro(rule))
+        } else if input.peek(syn::Token![use]) {
+            let rule = input.parse::<UseRule>()?;
+            if vis == Vis::Public {
+                synerr!(rule.span, "imported matchers can't be re-exported");
+            }
+            Ok(Rule::Use(rule))
         } else {
-            input.parse::<MatchRule>().map(Rule::Match)
+            let mut rule = input.parse::<MatchRule>()?;
+            rule.vis = vis;
+            Ok(Rule::Match(rule))
         }
     }
 }
 
 #[derive(Debug)]
 pub(crate) struct Rules {
-    pub(crate) macro_rule: MacroRule,
-    pub(crate) matches: HashMap<String, Box<[Patterns]>>,
+    pub(crate) macros: Box<[MacroRule]>,
+    pub(crate) functions: Box<[FnRule]>,
+    pub(crate) matches: HashMap<String, Box<[Alternative]>>,
+    pub(crate) options: Options,
+}
+
+/// The input of `inception::rules!`
+pub(crate) enum RulesInput {
+    Rules {
+        rules: Rules,
+        /// names of the `pub match` rules
+        exports: Vec<String>,
+    },
+    /// A matcher imported from another `rules!` block, which must be requested from the macro
+    /// generated for its `pub match` before the rules can be compiled
+    Import(UseRule),
+    /// A matcher that is used, but neither defined nor imported explicitly, which must be
+    /// requested from the glob imports. It is provided as `@import [name]`.
+    GlobImport { name: String, globs: Vec<UseRule> },
 }
 
-impl Parse for Rules {
+impl Parse for RulesInput {
     fn parse(input: ParseStream) -> syn::Result<Self> {
+        // matchers provided by the macros of `pub match` rules: `@import [path] { ... }`
+        let mut provided = Vec::new();
+        while input.peek(syn::Token![@]) {
+            provided.push(parse_provided(input)?);
+        }
+
+        let options = input.parse::<Options>()?;
+
         let mut parsed_rules = Vec::new();
         while !input.is_empty() {
             parsed_rules.push(input.parse::<Rule>()?);
         }
 
-        let mut macro_rule = None;
+        let mut macros = Vec::<MacroRule>::new();
+        let mut functions = Vec::<FnRule>::new();
         let mut matches = HashMap::new();
+        let mut exports = Vec::new();
+        let mut imported = HashMap::new();
+        let mut globs = Vec::new();
+        let mut used = BTreeSet::new();
 
         for rule in parsed_rules {
             match rule {
                 Rule::Match(match_rule) => {
+                    for alternative in &match_rule.pattern_set {
+                        collect_used(&alternative.patterns.0, &mut used);
+                    }
                     if matches.contains_key(&match_rule.name) {
                         synerr!(Span::call_site(), "duplicate matcher name {}", match_rule.name);
                     }
+                    if match_rule.vis == Vis::Public {
+                        exports.push(match_rule.name.clone());
+                    }
                     matches.insert(match_rule.name, match_rule.pattern_set);
                 }
                 Rule::Macro(rule) => {
-                    if macro_rule.is_some() {
-                        synerr!(Span::call_site(), "inception rules contain multiple macros");
+                    collect_used(&rule.patterns.0, &mut used);
+                    if macros.iter().any(|m| m.name == rule.name) {
+                        synerr!(Span::call_site(), "duplicate macro name {}", rule.name);
+                    }
+                    macros.push(rule);
+                }
+                Rule::Fn(rule) => {
+                    if functions.iter().any(|f| f.matcher == rule.matcher && f.name == rule.name) {
+                        synerr!(
+                            Span::call_site(),
+                            "duplicate function name {}.{}",
+                            rule.matcher,
+                            rule.name
+                        );
                     }
-                    macro_rule = Some(rule);
+                    collect_used(&rule.params.0, &mut used);
+                    functions.push(rule);
+                }
+                Rule::Use(use_rule) if grammar::is_builtin(&use_rule) => {
+                    imported.extend(grammar::import(&use_rule)?);
+                }
+                Rule::Use(use_rule) if use_rule.path.last().is_some_and(|s| s == "*") => {
+                    globs.push(use_rule);
                 }
+                Rule::Use(use_rule) => {
+                    let Some(idx) = provided.iter().position(|(path, _)| *path == use_rule.path)
+                    else {
+                        return Ok(RulesInput::Import(use_rule));
+                    };
+                    let (_, provided_matches) = provided.swap_remove(idx);
+                    for (name, pattern_set) in provided_matches {
+                        if matches.contains_key(&name) {
+                            synerr!(use_rule.span, "duplicate matcher name {name}");
+                        }
+                        matches.insert(name, pattern_set);
+                    }
+                }
+            }
+        }
+
+        // glob imports provide the matchers that are used, but neither defined nor imported
+        let missing = used
+            .into_iter()
+            .filter(|name| !matches.contains_key(name) && !imported.contains_key(name))
+            .filter(|name| !expand::is_builtin_matcher(name))
+            .collect::<Vec<_>>();
+        if !globs.is_empty() {
+            for name in missing {
+                let Some(idx) = provided.iter().position(|(path, _)| *path == [name.as_str()])
+                else {
+                    return Ok(RulesInput::GlobImport { name, globs });
+                };
+                let (_, provided_matches) = provided.swap_remove(idx);
+                imported.extend(provided_matches);
             }
         }
-        let Some(macro_rule) = macro_rule else {
-            synerr!(Span::call_site(), "does not declare a `pub macro`");
+
+        // matchers defined or imported explicitly take precedence over glob imports
+        for (name, pattern_set) in imported {
+            matches.entry(name).or_insert(pattern_set);
+        }
+
+        shadowing::check_shadowing(&matches)?;
+
+        if macros.is_empty() && exports.is_empty() {
+            synerr!(Span::call_site(), "does not declare a macro or a `pub match`");
+        }
+        let rules = Rules {
+            macros: macros.into_boxed_slice(),
+            functions: functions.into_boxed_slice(),
+            matches,
+            options,
         };
-        Ok(Rules { macro_rule, matches })
+        Ok(RulesInput::Rules { rules, exports })
+    }
+}
+
+/// Collects the types of all matchers in `patterns`
+fn collect_used(patterns: &[Pattern], used: &mut BTreeSet<String>) {
+    for pattern in patterns {
+        match pattern {
+            Pattern::Group(group) => collect_used(&group.content, used),
+            Pattern::Matcher(matcher) => {
+                used.insert(matcher.ty.clone());
+            }
+            _ => {}
+        }
     }
 }
+
+type ProvidedMatches = (Vec<String>, HashMap<String, Box<[Alternative]>>);
+
+fn parse_provided(input: ParseStream) -> syn::Result<ProvidedMatches> {
+    input.parse::<syn::Token![@]>()?;
+    let kw = input.parse::<syn::Ident>()?;
+    if kw != "import" {
+        synerr!(kw.span(), "expected `import`");
+    }
+
+    let path;
+    syn::bracketed!(path in input);
+    let path = UseRule::parse_path(&path)?;
+
+    let encoded;
+    syn::braced!(encoded in input);
+    let matches = encoding::decode_matches(&encoded)?;
+
+    Ok((path, matches))
+}
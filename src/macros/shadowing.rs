@@ -0,0 +1,111 @@
+//! Detection of `match` rule alternatives that can never be chosen.
+
+use std::collections::HashMap;
+
+use super::{
+    Alternative, Literal,
+    pattern::{Pattern, PatternMatcher, Patterns},
+};
+
+/// Returns an error if an alternative of a `match` rule is shadowed by an earlier alternative,
+/// i.e. if the earlier alternative matches everything that the later one matches.
+///
+/// This is conservative: only shadowing that is evident from the syntax is detected. For
+/// example, `($tt)` shadows `(foo)`, but `($expr)` is not known to shadow `($ident)`.
+pub(crate) fn check_shadowing(matches: &HashMap<String, Box<[Alternative]>>) -> syn::Result<()> {
+    let mut names = matches.keys().collect::<Vec<_>>();
+    names.sort();
+
+    for name in names {
+        let pattern_set = &matches[name];
+        for (later_idx, later) in pattern_set.iter().enumerate() {
+            let Patterns(later_patterns) = &later.patterns;
+            let earlier = pattern_set[..later_idx].iter().position(|earlier| {
+                let Patterns(earlier_patterns) = &earlier.patterns;
+                subsumes_all(matches, earlier_patterns, later_patterns)
+            });
+
+            if let Some(earlier_idx) = earlier {
+                synerr!(
+                    later.span,
+                    "{} of `{name}` is unreachable, because {} matches the same input",
+                    later.describe(later_idx),
+                    pattern_set[earlier_idx].describe(earlier_idx)
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+fn subsumes_all(
+    matches: &HashMap<String, Box<[Alternative]>>,
+    a: &[Pattern],
+    b: &[Pattern],
+) -> bool {
+    a.len() == b.len() && a.iter().zip(b).all(|(a, b)| subsumes(matches, a, b))
+}
+
+/// Whether `a` matches every token sequence that `b` matches
+fn subsumes(matches: &HashMap<String, Box<[Alternative]>>, a: &Pattern, b: &Pattern) -> bool {
+    match (a, b) {
+        (Pattern::Group(a), Pattern::Group(b)) => {
+            a.delimiter == b.delimiter && subsumes_all(matches, &a.content, &b.content)
+        }
+        (Pattern::Ident(a), Pattern::Ident(b)) => a == b,
+        (Pattern::Punct(a), Pattern::Punct(b)) => a.char == b.char,
+        (Pattern::Literal(Literal(a)), Pattern::Literal(Literal(b))) => a == b,
+        (Pattern::Matcher(a), Pattern::Matcher(b)) if a.ty == b.ty => same_repeat(a, b),
+        (Pattern::Matcher(a), b) if a.repeat.is_none() && !matches.contains_key(&a.ty) => {
+            let single_token = match b {
+                Pattern::Matcher(b) if b.repeat.is_some() || matches.contains_key(&b.ty) => {
+                    return false;
+                }
+                Pattern::Matcher(b) => b.ty.as_str(),
+                Pattern::Group(_) => "group",
+                Pattern::Ident(_) => "ident",
+                Pattern::Punct(_) => "punct",
+                Pattern::Literal(_) => "literal",
+            };
+            match a.ty.as_str() {
+                "tt" => matches!(
+                    single_token,
+                    "tt" | "group"
+                        | "ident"
+                        | "punct"
+                        | "literal"
+                        | "bool"
+                        | "str"
+                        | "byte_str"
+                        | "c_str"
+                        | "char"
+                        | "byte"
+                ),
+                "ident" => matches!(single_token, "ident" | "bool"),
+                "literal" => {
+                    matches!(
+                        single_token,
+                        "literal" | "str" | "byte_str" | "c_str" | "char" | "byte"
+                    )
+                }
+                _ => false,
+            }
+        }
+        _ => false,
+    }
+}
+
+fn same_repeat(a: &PatternMatcher, b: &PatternMatcher) -> bool {
+    match (&a.repeat, &b.repeat) {
+        (None, None) => true,
+        (Some(a), Some(b)) => {
+            a.quantifier == b.quantifier
+                && match (a.interspersed, b.interspersed) {
+                    (None, None) => true,
+                    (Some(a), Some(b)) => a.kind == b.kind && a.punct.char == b.punct.char,
+                    _ => false,
+                }
+        }
+        _ => false,
+    }
+}
@@ -0,0 +1,37 @@
+use proc_macro2::Span;
+use syn::{
+    ext::IdentExt,
+    parse::{Parse, ParseStream},
+};
+
+/// `use rust_parser::v1;`, `use path::to::matcher;` or `use path::to::grammar::*;`
+#[derive(Debug)]
+pub(crate) struct UseRule {
+    pub(crate) path: Vec<String>,
+    pub(crate) span: Span,
+}
+
+impl UseRule {
+    pub(crate) fn parse_path(input: ParseStream) -> syn::Result<Vec<String>> {
+        let ident = input.call(syn::Ident::parse_any)?;
+        let mut path = vec![ident.to_string()];
+        while input.peek(syn::Token![::]) {
+            input.parse::<syn::Token![::]>()?;
+            if input.parse::<Option<syn::Token![*]>>()?.is_some() {
+                path.push("*".to_string());
+                break;
+            }
+            path.push(input.call(syn::Ident::parse_any)?.to_string());
+        }
+        Ok(path)
+    }
+}
+
+impl Parse for UseRule {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let span = input.parse::<syn::Token![use]>()?.span;
+        let path = UseRule::parse_path(input)?;
+        input.parse::<syn::Token![;]>()?;
+        Ok(UseRule { path, span })
+    }
+}
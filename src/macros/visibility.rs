@@ -1,6 +1,6 @@
 use syn::parse::{Parse, ParseStream};
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Clone, Copy)]
 pub(crate) enum Vis {
     Private,
     Public,